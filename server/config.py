@@ -24,6 +24,10 @@ class Settings(BaseSettings):
     TASK_CONFIDENCE_THRESHOLD: float = 0.75  # Minimum confidence to create a task
     TASK_DETECTION_ENABLED: bool = True  # Master switch for task detection
 
+    # Logging
+    OTLP_ENDPOINT: str = ""  # OTLP/HTTP collector URL; empty disables export
+    DEBUG_LOG_ROUTE: bool = False  # Serve recent log records at /api/debug/logs
+
     model_config = SettingsConfigDict(
         env_file=Path(__file__).parent / ".env",
         env_file_encoding="utf-8",
@@ -0,0 +1,153 @@
+use chrono::{DateTime, FixedOffset, Local, Offset, TimeZone, Timelike, Utc};
+use chrono_tz::Tz;
+use std::sync::Mutex;
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Zone {
+    Utc,
+    Local,
+    Named(Tz),
+}
+
+impl Zone {
+    /// `utc`, `local` or an IANA name such as `Europe/Berlin`.
+    pub fn parse(s: &str) -> Option<Self> {
+        match s.to_lowercase().as_str() {
+            "utc" | "z" => Some(Zone::Utc),
+            "local" => Some(Zone::Local),
+            _ => s.parse().ok().map(Zone::Named),
+        }
+    }
+
+    fn offset(self, t: &DateTime<Utc>) -> FixedOffset {
+        match self {
+            Zone::Utc => FixedOffset::east_opt(0).unwrap(),
+            Zone::Local => Local.offset_from_utc_datetime(&t.naive_utc()).fix(),
+            Zone::Named(tz) => tz.offset_from_utc_datetime(&t.naive_utc()).fix(),
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Precision {
+    Seconds,
+    Millis,
+    Micros,
+    Nanos,
+}
+
+impl Precision {
+    pub fn parse(s: &str) -> Option<Self> {
+        match s.to_lowercase().as_str() {
+            "s" | "seconds" => Some(Precision::Seconds),
+            "ms" | "millis" => Some(Precision::Millis),
+            "us" | "micros" => Some(Precision::Micros),
+            "ns" | "nanos" => Some(Precision::Nanos),
+            _ => None,
+        }
+    }
+}
+
+/// The whole-second part of the last timestamp; only the fraction changes
+/// within a second.
+struct Cached {
+    secs: i64,
+    offset: FixedOffset,
+    head: String,
+    tail: String,
+}
+
+/// Formats RFC 3339 timestamps in a fixed zone and precision.
+pub struct Clock {
+    zone: Zone,
+    precision: Precision,
+    cache: Mutex<Option<Cached>>,
+}
+
+impl Clock {
+    pub fn new(zone: Zone, precision: Precision) -> Self {
+        Clock { zone, precision, cache: Mutex::new(None) }
+    }
+
+    /// Returns `now` in this clock's zone along with its formatted timestamp.
+    pub fn stamp(&self, now: DateTime<Utc>) -> (DateTime<FixedOffset>, String) {
+        let mut cache = self.cache.lock().unwrap();
+        let c = match &mut *cache {
+            Some(c) if c.secs == now.timestamp() => c,
+            slot => {
+                let offset = self.zone.offset(&now);
+                let local = now.with_timezone(&offset);
+                let tail = if self.zone == Zone::Utc { "Z".into() } else { local.format("%:z").to_string() };
+                slot.insert(Cached {
+                    secs: now.timestamp(),
+                    offset,
+                    head: local.format("%Y-%m-%dT%H:%M:%S").to_string(),
+                    tail,
+                })
+            }
+        };
+        // Leap seconds report a nanosecond count past 999_999_999.
+        let nanos = now.nanosecond().min(999_999_999);
+        let fraction = match self.precision {
+            Precision::Seconds => String::new(),
+            Precision::Millis => format!(".{:03}", nanos / 1_000_000),
+            Precision::Micros => format!(".{:06}", nanos / 1_000),
+            Precision::Nanos => format!(".{:09}", nanos),
+        };
+        (now.with_timezone(&c.offset), format!("{}{}{}", c.head, fraction, c.tail))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(s: &str) -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339(s).unwrap().with_timezone(&Utc)
+    }
+
+    fn stamp(clock: &Clock, s: &str) -> String {
+        clock.stamp(at(s)).1
+    }
+
+    #[test]
+    fn cache_follows_second_boundaries() {
+        let clock = Clock::new(Zone::Utc, Precision::Millis);
+        assert_eq!(stamp(&clock, "2026-10-16T23:59:59.998Z"), "2026-10-16T23:59:59.998Z");
+        assert_eq!(stamp(&clock, "2026-10-16T23:59:59.999Z"), "2026-10-16T23:59:59.999Z");
+        assert_eq!(stamp(&clock, "2026-10-17T00:00:00.001Z"), "2026-10-17T00:00:00.001Z");
+        // A clock stepping backwards must not reuse the newer second.
+        assert_eq!(stamp(&clock, "2026-10-16T23:59:59.500Z"), "2026-10-16T23:59:59.500Z");
+    }
+
+    #[test]
+    fn precisions() {
+        let t = "2026-10-16T09:05:03.123456789Z";
+        let cases = [
+            (Precision::Seconds, "2026-10-16T09:05:03Z"),
+            (Precision::Millis, "2026-10-16T09:05:03.123Z"),
+            (Precision::Micros, "2026-10-16T09:05:03.123456Z"),
+            (Precision::Nanos, "2026-10-16T09:05:03.123456789Z"),
+        ];
+        for (precision, want) in cases {
+            assert_eq!(stamp(&Clock::new(Zone::Utc, precision), t), want);
+        }
+    }
+
+    #[test]
+    fn named_zone_across_dst_change() {
+        let clock = Clock::new(Zone::parse("Europe/Berlin").unwrap(), Precision::Seconds);
+        // Clocks go back at 01:00 UTC on 2026-10-25.
+        assert_eq!(stamp(&clock, "2026-10-25T00:59:59Z"), "2026-10-25T02:59:59+02:00");
+        assert_eq!(stamp(&clock, "2026-10-25T01:00:00Z"), "2026-10-25T02:00:00+01:00");
+        let (local, _) = clock.stamp(at("2026-10-25T01:00:00Z"));
+        assert_eq!(local.offset().local_minus_utc(), 3600);
+    }
+
+    #[test]
+    fn parses_zones() {
+        assert!(Zone::parse("UTC") == Some(Zone::Utc));
+        assert!(Zone::parse("local") == Some(Zone::Local));
+        assert!(Zone::parse("Mars/Olympus").is_none());
+    }
+}
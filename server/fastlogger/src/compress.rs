@@ -0,0 +1,252 @@
+use crossbeam_channel::{unbounded, Sender};
+use flate2::write::GzEncoder;
+use std::fs::{File, OpenOptions};
+use std::io::{self, BufReader, BufWriter, Write};
+use std::os::unix::fs::MetadataExt;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::{Duration, Instant, SystemTime};
+
+use crate::lock::FileLock;
+use crate::rotation;
+
+/// Codec applied to rotated backups.
+#[derive(Clone, Copy, PartialEq)]
+pub enum Compression {
+    Gzip,
+    Zstd,
+}
+
+impl Compression {
+    pub fn parse(s: &str) -> Option<Self> {
+        match s.to_lowercase().as_str() {
+            "gzip" | "gz" => Some(Compression::Gzip),
+            "zstd" | "zst" => Some(Compression::Zstd),
+            _ => None,
+        }
+    }
+
+    pub fn ext(self) -> &'static str {
+        match self {
+            Compression::Gzip => ".gz",
+            Compression::Zstd => ".zst",
+        }
+    }
+}
+
+/// Every suffix a backup may carry, uncompressed first.
+pub const EXTS: [&str; 3] = ["", ".gz", ".zst"];
+
+/// Suffix written while a backup is being compressed.
+pub const PARTIAL: &str = ".part";
+
+/// How long a fresh backup is left alone before it is compressed. Other
+/// processes may still be appending to it until they notice the rotation.
+const SETTLE: Duration = Duration::from_secs(3);
+
+/// A partial output untouched this long belongs to a sweep that died.
+const ABANDONED: Duration = Duration::from_secs(600);
+
+/// Strips a compression extension from a backup suffix.
+pub fn strip_ext(suffix: &str) -> &str {
+    EXTS[1..]
+        .iter()
+        .find_map(|e| suffix.strip_suffix(e))
+        .unwrap_or(suffix)
+}
+
+/// A backup being compressed into `part`, created by whoever claimed it.
+struct Claim {
+    /// Opened when claimed, so a rotation renaming the backup meanwhile
+    /// does not matter.
+    input: File,
+    part: PathBuf,
+    out: File,
+    /// Identifies the backup after a rotation renames it.
+    id: (u64, u64),
+}
+
+fn file_id(p: &Path) -> Option<(u64, u64)> {
+    std::fs::metadata(p).ok().map(|m| (m.dev(), m.ino()))
+}
+
+/// Claims every settled, uncompressed backup of `path` by creating its
+/// partial output, so no other sweep compresses it too. Partials nobody has
+/// written to for `ABANDONED` are left from an interrupted sweep and go.
+fn claim(path: &str, codec: Compression) -> Vec<Claim> {
+    let age = |p: &Path| {
+        std::fs::metadata(p)
+            .and_then(|m| m.modified())
+            .map(|t| SystemTime::now().duration_since(t).unwrap_or_default())
+    };
+    let mut claims = Vec::new();
+    for (p, suffix) in rotation::backups(path) {
+        if suffix.ends_with(PARTIAL) {
+            if age(&p).is_ok_and(|a| a >= ABANDONED) {
+                std::fs::remove_file(&p).ok();
+            }
+            continue;
+        }
+        if strip_ext(&suffix) != suffix || age(&p).map_or(true, |a| a < SETTLE) {
+            continue;
+        }
+        let (Some(id), Ok(input)) = (file_id(&p), File::open(&p)) else { continue };
+        let part = PathBuf::from(format!("{}{}{}", p.display(), codec.ext(), PARTIAL));
+        if let Ok(out) = OpenOptions::new().write(true).create_new(true).open(&part) {
+            claims.push(Claim { input, part, out, id });
+        }
+    }
+    claims
+}
+
+impl Claim {
+    fn encode(&self, codec: Compression) -> io::Result<()> {
+        let mut input = BufReader::new(&self.input);
+        let out = BufWriter::new(&self.out);
+        match codec {
+            Compression::Gzip => {
+                let mut enc = GzEncoder::new(out, flate2::Compression::default());
+                io::copy(&mut input, &mut enc)?;
+                enc.finish()?.flush()
+            }
+            Compression::Zstd => {
+                let mut enc = zstd::Encoder::new(out, 0)?;
+                io::copy(&mut input, &mut enc)?;
+                enc.finish()?.flush()
+            }
+        }
+    }
+
+    /// Moves the compressed copy next to the backup, wherever rotation has
+    /// moved it by now, and removes the original.
+    fn finish(self, path: &str, codec: Compression, encoded: io::Result<()>) {
+        let src = rotation::backups(path)
+            .into_iter()
+            .filter(|(_, s)| strip_ext(s) == s && !s.ends_with(PARTIAL))
+            .find(|(p, _)| file_id(p) == Some(self.id));
+        match (encoded, src) {
+            (Ok(()), Some((src, _))) => {
+                std::fs::rename(&self.part, format!("{}{}", src.display(), codec.ext())).ok();
+                std::fs::remove_file(&src).ok();
+            }
+            // Failed, or pruned while we worked.
+            _ => {
+                std::fs::remove_file(&self.part).ok();
+            }
+        }
+    }
+}
+
+/// Compresses every uncompressed backup of `path`.
+///
+/// Sweeping rather than compressing one named file keeps this correct when
+/// a rotation shifts `x.log.1` to `x.log.2` before its job gets to run.
+/// Only names the rotator writes count, so `x.log.bak` and friends are
+/// left as they are.
+///
+/// The sidecar lock and then `lock`, the order rotation takes them in, are
+/// held only to claim backups and to rename the results; encoding runs
+/// without them so a rotation never waits for it.
+fn sweep(path: &str, codec: Compression, lock: &Mutex<()>) {
+    let claims = {
+        // The next rotation schedules another sweep.
+        let Ok(_flock) = FileLock::acquire(path) else { return };
+        let _guard = lock.lock().unwrap();
+        claim(path, codec)
+    };
+    for c in claims {
+        let encoded = c.encode(codec);
+        let Ok(_flock) = FileLock::acquire(path) else {
+            std::fs::remove_file(&c.part).ok();
+            continue;
+        };
+        let _guard = lock.lock().unwrap();
+        c.finish(path, codec, encoded);
+    }
+}
+
+struct Job {
+    due: Instant,
+    path: String,
+    codec: Compression,
+    lock: Arc<Mutex<()>>,
+}
+
+fn queue() -> &'static Sender<Job> {
+    static QUEUE: OnceLock<Sender<Job>> = OnceLock::new();
+    QUEUE.get_or_init(|| {
+        let (tx, rx) = unbounded::<Job>();
+        std::thread::Builder::new()
+            .name("fastlogger-compress".into())
+            .spawn(move || {
+                for job in rx.iter() {
+                    std::thread::sleep(job.due.saturating_duration_since(Instant::now()));
+                    sweep(&job.path, job.codec, &job.lock);
+                }
+            })
+            .expect("failed to spawn fastlogger compression thread");
+        tx
+    })
+}
+
+/// Schedules compression of `path`'s backups on the shared compression thread.
+/// `lock` is the one held by the owning file while it renames backups, taken
+/// after the sidecar lock.
+pub fn schedule(path: &str, codec: Compression, lock: Arc<Mutex<()>>) {
+    queue().send(Job { due: Instant::now() + SETTLE, path: path.to_string(), codec, lock }).ok();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sweep_compresses_only_backups() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("chat.log");
+        let settled = SystemTime::now() - SETTLE * 2;
+        for name in ["chat.log.1", "chat.log.2026-10-15", "chat.log.bak", "chat.log.notes.part", "chat.log.2.gz.part"] {
+            let f = File::create(dir.path().join(name)).unwrap();
+            f.set_modified(settled).unwrap();
+        }
+        // Another sweep may still be writing a recent partial.
+        let f = File::create(dir.path().join("chat.log.3.gz.part")).unwrap();
+        f.set_modified(SystemTime::now() - ABANDONED * 2).unwrap();
+        sweep(path.to_str().unwrap(), Compression::Gzip, &Mutex::new(()));
+        let mut left: Vec<_> = std::fs::read_dir(dir.path())
+            .unwrap()
+            .map(|e| e.unwrap().file_name().into_string().unwrap())
+            .filter(|n| !n.starts_with('.'))
+            .collect();
+        left.sort();
+        let want = ["chat.log.1.gz", "chat.log.2.gz.part", "chat.log.2026-10-15.gz", "chat.log.bak"];
+        assert_eq!(left[..4], want);
+        assert_eq!(left[4], "chat.log.notes.part");
+    }
+
+    #[test]
+    fn rotation_while_compressing_keeps_the_chain() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("chat.log");
+        let path = path.to_str().unwrap();
+        std::fs::write(format!("{}.1", path), "old\n").unwrap();
+        let backup = File::options().write(true).open(format!("{}.1", path)).unwrap();
+        backup.set_modified(SystemTime::now() - SETTLE * 2).unwrap();
+        let claims = claim(path, Compression::Gzip);
+        assert_eq!(claims.len(), 1);
+        // Neither lock is held while encoding, so a rotation can run now.
+        std::fs::write(path, "new\n").unwrap();
+        rotation::rotate_numbered(path, 5).unwrap();
+        for c in claims {
+            let encoded = c.encode(Compression::Gzip);
+            c.finish(path, Compression::Gzip, encoded);
+        }
+        let mut old = String::new();
+        let gz = File::open(format!("{}.2.gz", path)).unwrap();
+        io::Read::read_to_string(&mut flate2::read::GzDecoder::new(gz), &mut old).unwrap();
+        assert_eq!(old, "old\n");
+        assert_eq!(std::fs::read_to_string(format!("{}.1", path)).unwrap(), "new\n");
+        assert!(!Path::new(&format!("{}.2", path)).exists());
+        assert!(!Path::new(&format!("{}.1.gz.part", path)).exists());
+    }
+}
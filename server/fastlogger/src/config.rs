@@ -0,0 +1,585 @@
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+use serde_json::{Map, Value};
+use std::collections::{HashMap, HashSet};
+use std::io;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use crate::clock::{Clock, Precision, Zone};
+use crate::compress::Compression;
+use crate::console::{Color, Console};
+use crate::error::{self, Errors, OnError};
+use crate::fields;
+use crate::journald::{self, Journald};
+use crate::file::FileTarget;
+use crate::format::{Format, Formatter, Newlines};
+use crate::level::LogLevel;
+use crate::otlp::{self, Endpoint, OtlpSpec};
+use crate::registry;
+use crate::rotation::{Policy, Settings};
+use crate::sink::{self, Kind, SinkSpec};
+use crate::syslog::{self, Protocol, Syslog, Transport};
+use crate::writer::{Overflow, Writer};
+
+/// Everything needed to open a log file; two loggers with equal specs for
+/// the same path share a writer.
+#[derive(Clone, PartialEq)]
+pub struct FileSpec {
+    pub path: String,
+    pub rotation: Settings,
+    pub background: bool,
+    pub queue_size: usize,
+    pub overflow: Overflow,
+    pub on_error: OnError,
+}
+
+impl FileSpec {
+    pub fn new(path: String) -> Self {
+        FileSpec {
+            path,
+            rotation: Settings {
+                max_bytes: 10 * 1024 * 1024,
+                backup_count: 5,
+                policy: Policy { size: true, interval: None },
+                max_age: None,
+                compression: None,
+            },
+            background: false,
+            queue_size: 10000,
+            overflow: Overflow::Block,
+            on_error: OnError::Raise,
+        }
+    }
+
+    /// Returns the shared writer for this file. With `replace`, a live writer
+    /// opened with different settings is switched to these ones in place.
+    pub fn open(&self, replace: bool) -> PyResult<Arc<Writer>> {
+        let target = || FileTarget::new(self.path.clone(), self.rotation).map_err(|e| error::to_py(&self.path, e));
+        registry::writer(
+            self,
+            replace,
+            || {
+                let errors = Errors::new(&self.path, self.on_error);
+                Ok(if self.background {
+                    Writer::background(target()?, self.queue_size, self.overflow, errors)
+                } else {
+                    Writer::direct(target()?, errors)
+                })
+            },
+            |w| w.reconfigure(self.on_error, self.background.then_some((self.queue_size, self.overflow)), target),
+        )
+    }
+}
+
+/// Parses a named option, raising `ValueError("unknown <what>: <s>")`.
+pub fn choice<T>(what: &str, s: &str, parse: impl Fn(&str) -> Option<T>) -> PyResult<T> {
+    parse(s).ok_or_else(|| PyValueError::new_err(format!("unknown {}: {}", what, s)))
+}
+
+pub fn days(max_age_days: f64) -> PyResult<Duration> {
+    Duration::try_from_secs_f64(max_age_days * 86400.0)
+        .map_err(|_| PyValueError::new_err("max_age_days must be a non-negative number"))
+}
+
+/// Why a configuration was rejected. Kept apart from `PyErr` so checking a
+/// document needs no interpreter.
+pub enum Rejected {
+    /// Raised as `ValueError` with this message.
+    Invalid(String),
+    /// A sink could not be set up; raised as `LogError`.
+    Io(String, io::Error),
+}
+
+impl From<Rejected> for PyErr {
+    fn from(r: Rejected) -> PyErr {
+        match r {
+            Rejected::Invalid(msg) => PyValueError::new_err(msg),
+            Rejected::Io(path, e) => error::to_py(&path, e),
+        }
+    }
+}
+
+type Result<T> = std::result::Result<T, Rejected>;
+
+fn invalid(at: &str, msg: impl std::fmt::Display) -> Rejected {
+    Rejected::Invalid(format!("fastlogger config: {}: {}", at, msg))
+}
+
+/// A TOML/dict table being read, which rejects keys nobody asked for.
+struct Table<'a> {
+    at: String,
+    map: &'a Map<String, Value>,
+    used: HashSet<&'a str>,
+}
+
+impl<'a> Table<'a> {
+    fn new(at: &str, value: &'a Value) -> Result<Self> {
+        match value {
+            Value::Object(map) => Ok(Table { at: at.to_string(), map, used: HashSet::new() }),
+            _ => Err(invalid(at, "expected a table")),
+        }
+    }
+
+    fn key(&self, k: &str) -> String {
+        if self.at.is_empty() { k.to_string() } else { format!("{}.{}", self.at, k) }
+    }
+
+    fn get(&mut self, k: &'a str) -> Option<&'a Value> {
+        self.used.insert(k);
+        self.map.get(k)
+    }
+
+    fn str(&mut self, k: &'a str) -> Result<Option<&'a str>> {
+        match self.get(k) {
+            None => Ok(None),
+            Some(Value::String(s)) => Ok(Some(s)),
+            Some(_) => Err(invalid(&self.key(k), "expected a string")),
+        }
+    }
+
+    fn uint(&mut self, k: &'a str) -> Result<Option<u64>> {
+        match self.get(k) {
+            None => Ok(None),
+            Some(v) => v.as_u64().map(Some).ok_or_else(|| invalid(&self.key(k), "expected a non-negative integer")),
+        }
+    }
+
+    fn float(&mut self, k: &'a str) -> Result<Option<f64>> {
+        match self.get(k) {
+            None => Ok(None),
+            Some(v) => v.as_f64().map(Some).ok_or_else(|| invalid(&self.key(k), "expected a number")),
+        }
+    }
+
+    fn bool(&mut self, k: &'a str) -> Result<Option<bool>> {
+        match self.get(k) {
+            None => Ok(None),
+            Some(v) => v.as_bool().map(Some).ok_or_else(|| invalid(&self.key(k), "expected true or false")),
+        }
+    }
+
+    fn choice<T>(&mut self, k: &'a str, parse: impl Fn(&str) -> Option<T>) -> Result<Option<T>> {
+        match self.str(k)? {
+            None => Ok(None),
+            Some(s) => parse(s).map(Some).ok_or_else(|| invalid(&self.key(k), format!("unknown value {:?}", s))),
+        }
+    }
+
+    fn level(&mut self, k: &'a str) -> Result<Option<LogLevel>> {
+        match self.get(k) {
+            None => Ok(None),
+            Some(v) => level_value(&self.key(k), v).map(Some),
+        }
+    }
+
+    fn done(self) -> Result<()> {
+        match self.map.keys().find(|k| !self.used.contains(k.as_str())) {
+            Some(k) => Err(invalid(&self.key(k), "unknown key")),
+            None => Ok(()),
+        }
+    }
+}
+
+fn level_value(at: &str, v: &Value) -> Result<LogLevel> {
+    let parsed = match v {
+        Value::String(s) => LogLevel::parse(s),
+        Value::Number(n) => n.as_i64().map(LogLevel::from_number),
+        _ => None,
+    };
+    parsed.ok_or_else(|| invalid(at, format!("unknown log level {}", v)))
+}
+
+/// Reads the formatter keys any sink may have; `default_fmt` applies to
+/// text output without an explicit `fmt`.
+fn formatter(t: &mut Table, default_fmt: Option<&str>, default_newlines: Newlines) -> Result<Formatter> {
+    let format = t.choice("format", Format::parse)?.unwrap_or(Format::Text);
+    let fmt = match (t.str("fmt")?, &format) {
+        (None, Format::Text) => default_fmt,
+        (fmt, _) => fmt,
+    };
+    let format = format.with_template(fmt).map_err(|e| invalid(&t.key("fmt"), e))?;
+    let zone = t.choice("timezone", Zone::parse)?.unwrap_or(Zone::Utc);
+    let precision = t.choice("precision", Precision::parse)?.unwrap_or(Precision::Millis);
+    let mut formatter = Formatter::new(format, Clock::new(zone, precision));
+    formatter.newlines = t.choice("newlines", Newlines::parse)?.unwrap_or(default_newlines);
+    formatter.max_message_bytes = t.uint("max_message_bytes")?.map(|v| v as usize);
+    Ok(formatter)
+}
+
+fn file(t: &mut Table) -> Result<FileSpec> {
+    let path = t.str("path")?.ok_or_else(|| invalid(&t.key("path"), "missing"))?;
+    let mut f = FileSpec::new(path.to_string());
+    let r = &mut f.rotation;
+    if let Some(v) = t.uint("max_bytes")? {
+        r.max_bytes = v;
+    }
+    if let Some(v) = t.uint("backup_count")? {
+        r.backup_count = v as usize;
+    }
+    if let Some(v) = t.choice("when", Policy::parse)? {
+        r.policy = v;
+    }
+    if let Some(v) = t.float("max_age_days")? {
+        let age = Duration::try_from_secs_f64(v * 86400.0);
+        r.max_age = Some(age.map_err(|_| invalid(&t.key("max_age_days"), "expected a non-negative number of days"))?);
+    }
+    r.compression = t.choice("compress", Compression::parse)?;
+    f.background = t.bool("background")?.unwrap_or(false);
+    if let Some(v) = t.uint("queue_size")? {
+        f.queue_size = v as usize;
+    }
+    if let Some(v) = t.choice("overflow", Overflow::parse)? {
+        f.overflow = v;
+    }
+    if let Some(v) = t.choice("on_error", OnError::parse)? {
+        f.on_error = v;
+    }
+    Ok(f)
+}
+
+fn syslog(t: &mut Table) -> Result<Syslog> {
+    let address = t.str("address")?.unwrap_or("/dev/log").to_string();
+    let transport = match t.choice("transport", Transport::parse)? {
+        Some(v) => v,
+        None => Transport::infer(&address),
+    };
+    let protocol = t.choice("protocol", Protocol::parse)?.unwrap_or(Protocol::Rfc5424);
+    let facility = t.choice("facility", syslog::facility)?.unwrap_or(1);
+    let on_error = t.choice("on_error", OnError::parse)?.unwrap_or(OnError::Raise);
+    let mut s = Syslog::new(address.clone(), transport, protocol, facility, Errors::new(&address, on_error));
+    s.app_name = t.str("app_name")?.map(str::to_string);
+    Ok(s)
+}
+
+fn sink(at: &str, value: &Value) -> Result<SinkSpec> {
+    let mut t = Table::new(at, value)?;
+    let kind = t.str("type")?.ok_or_else(|| invalid(&t.key("type"), "missing"))?;
+    let kind = match kind {
+        "file" => Kind::File(file(&mut t)?),
+        "console" => Kind::Console(Console::new(t.choice("color", Color::parse)?.unwrap_or(Color::Auto))),
+        "memory" => Kind::Memory(t.uint("capacity")?.unwrap_or(1000) as usize),
+        "ring_buffer" => Kind::Ring(t.uint("capacity")?.unwrap_or(1000) as usize),
+        "syslog" => Kind::Syslog(syslog(&mut t)?),
+        "journald" => {
+            let path = t.str("path")?.unwrap_or(journald::SOCKET).to_string();
+            let identifier = t.str("identifier")?.map(str::to_string);
+            let on_error = t.choice("on_error", OnError::parse)?.unwrap_or(OnError::Raise);
+            let mut j = Journald::new(path.clone(), Errors::new(&path, on_error)).map_err(|e| Rejected::Io(path, e))?;
+            j.identifier = identifier;
+            Kind::Journald(j)
+        }
+        "otlp" => Kind::Otlp(otlp(&mut t)?),
+        other => return Err(invalid(&t.key("type"), format!("unsupported sink type {:?}", other))),
+    };
+    let level = t.level("level")?;
+    let formatter = match kind {
+        Kind::Syslog(_) => formatter(&mut t, Some(sink::SYSLOG_FMT), Newlines::Escape)?,
+        Kind::Journald(_) => formatter(&mut t, Some(sink::JOURNALD_FMT), Newlines::Raw)?,
+        Kind::Otlp(_) => formatter(&mut t, Some(sink::OTLP_FMT), Newlines::Indent)?,
+        _ => formatter(&mut t, None, Newlines::Indent)?,
+    };
+    t.done()?;
+    Ok(SinkSpec { kind, level, formatter })
+}
+
+fn seconds(t: &Table, k: &str, v: f64) -> Result<Duration> {
+    Duration::try_from_secs_f64(v).map_err(|_| invalid(&t.key(k), "expected a non-negative number of seconds"))
+}
+
+fn otlp(t: &mut Table) -> Result<OtlpSpec> {
+    let url = t.str("endpoint")?.unwrap_or(otlp::ENDPOINT);
+    let endpoint = Endpoint::parse(url).map_err(|e| invalid(&t.key("endpoint"), e))?;
+    let mut o = otlp::Settings::new(endpoint, t.str("service_name")?.unwrap_or(otlp::SERVICE_NAME));
+    if let Some(v) = t.get("resource") {
+        let r = Table::new(&t.key("resource"), v)?;
+        fields::merge(&mut o.resource, r.map.iter().map(|(k, v)| (k.clone(), v.clone())));
+    }
+    if let Some(v) = t.get("headers") {
+        let h = Table::new(&t.key("headers"), v)?;
+        for (k, v) in h.map {
+            let v = v.as_str().ok_or_else(|| invalid(&h.key(k), "expected a string"))?;
+            otlp::check_header(k, v).map_err(|e| invalid(&h.key(k), e))?;
+            o.headers.push((k.clone(), v.to_string()));
+        }
+    }
+    if let Some(v) = t.uint("batch_size")? {
+        o.batch_size = (v as usize).max(1);
+    }
+    if let Some(v) = t.float("flush_interval")? {
+        o.flush_interval = seconds(t, "flush_interval", v)?;
+    }
+    if let Some(v) = t.uint("max_retries")? {
+        o.max_retries = v as u32;
+    }
+    if let Some(v) = t.float("retry_backoff")? {
+        o.retry_backoff = seconds(t, "retry_backoff", v)?;
+    }
+    if let Some(v) = t.float("timeout")? {
+        o.timeout = seconds(t, "timeout", v)?;
+    }
+    if let Some(v) = t.uint("queue_size")? {
+        o.queue_size = v as usize;
+    }
+    Ok(OtlpSpec {
+        settings: o,
+        on_error: t.choice("on_error", OnError::parse)?.unwrap_or(OnError::Raise),
+        fallback: None,
+        fallback_name: t.str("fallback")?.map(str::to_string),
+    })
+}
+
+/// A validated configuration, ready to apply.
+pub struct Plan {
+    pub levels: Vec<(String, LogLevel)>,
+    pub sinks: HashMap<String, SinkSpec>,
+    /// Each logger with the names of its sinks.
+    pub loggers: Vec<(String, Vec<String>)>,
+}
+
+/// Validates a configuration document:
+///
+/// ```toml
+/// [sinks.chat]
+/// type = "file"
+/// path = "logs/chat_service.log"
+/// format = "json"
+/// timezone = "utc"
+/// when = "daily"
+///
+/// [sinks.errors]
+/// type = "file"
+/// path = "logs/errors.log"
+/// level = "WARNING"
+///
+/// [sinks.console]
+/// type = "console"
+/// level = "INFO"
+///
+/// [sinks.rsyslog]
+/// type = "syslog"
+/// address = "/dev/log"
+/// facility = "local0"
+///
+/// [sinks.collector]
+/// type = "otlp"
+/// endpoint = "http://localhost:4318/v1/logs"
+/// fallback = "otlp_fallback"
+///
+/// [sinks.otlp_fallback]
+/// type = "file"
+/// path = "logs/otlp_fallback.log"
+///
+/// [loggers.chat_service]
+/// level = "DEBUG"
+/// sinks = ["chat", "errors", "console", "rsyslog", "collector"]
+///
+/// [levels]
+/// routes = "WARNING"
+/// ```
+pub fn plan(doc: &Value) -> Result<Plan> {
+    let mut root = Table::new("", doc)?;
+    let mut sinks = HashMap::new();
+    if let Some(v) = root.get("sinks") {
+        let t = Table::new("sinks", v)?;
+        for (name, spec) in t.map {
+            sinks.insert(name.clone(), sink(&t.key(name), spec)?);
+        }
+        for (name, spec) in &sinks {
+            if let Kind::Otlp(OtlpSpec { fallback_name: Some(f), .. }) = &spec.kind {
+                if !matches!(sinks.get(f), Some(SinkSpec { kind: Kind::File(_), .. })) {
+                    return Err(invalid(&t.key(&format!("{}.fallback", name)), format!("no file sink named {:?}", f)));
+                }
+            }
+        }
+    }
+    let mut levels = Vec::new();
+    if let Some(v) = root.get("levels") {
+        let t = Table::new("levels", v)?;
+        for (name, lvl) in t.map {
+            levels.push((name.clone(), level_value(&t.key(name), lvl)?));
+        }
+    }
+    let mut loggers = Vec::new();
+    if let Some(v) = root.get("loggers") {
+        let t = Table::new("loggers", v)?;
+        for (name, spec) in t.map {
+            let mut l = Table::new(&t.key(name), spec)?;
+            if let Some(lvl) = l.level("level")? {
+                levels.push((name.clone(), lvl));
+            }
+            let names = match l.get("sinks") {
+                Some(Value::Array(a)) if !a.is_empty() => a,
+                Some(_) => return Err(invalid(&l.key("sinks"), "expected a non-empty list of sink names")),
+                None => return Err(invalid(&l.key("sinks"), "missing")),
+            };
+            let mut used = Vec::new();
+            for n in names {
+                let n = n.as_str().ok_or_else(|| invalid(&l.key("sinks"), "expected a list of sink names"))?;
+                if !sinks.contains_key(n) {
+                    return Err(invalid(&l.key("sinks"), format!("no sink named {:?}", n)));
+                }
+                if !used.iter().any(|u| u == n) {
+                    used.push(n.to_string());
+                }
+            }
+            // A fallback only gets what the exporter could not send; listed
+            // as well, it would get every record twice.
+            for n in &used {
+                if let Some(SinkSpec { kind: Kind::Otlp(OtlpSpec { fallback_name: Some(f), .. }), .. }) = sinks.get(n) {
+                    if used.contains(f) {
+                        let msg = format!("{:?} is the fallback of {:?}; list only {:?}", f, n, n);
+                        return Err(invalid(&l.key("sinks"), msg));
+                    }
+                }
+            }
+            l.done()?;
+            loggers.push((name.clone(), used));
+        }
+    }
+    root.done()?;
+    Ok(Plan { levels, sinks, loggers })
+}
+
+/// Where the last configuration came from, for `reload()`.
+pub enum Source {
+    File(String),
+    Doc(Value),
+}
+
+static LAST: Mutex<Option<Source>> = Mutex::new(None);
+
+fn read(path: &str) -> Result<Value> {
+    let text = std::fs::read_to_string(path).map_err(|e| Rejected::Io(path.to_string(), e))?;
+    toml::from_str(&text).map_err(|e| invalid(path, e))
+}
+
+/// Reads a TOML file path or converts a dict.
+pub fn load(source: &Bound<'_, PyAny>) -> PyResult<(Value, Source)> {
+    if source.downcast::<pyo3::types::PyDict>().is_ok() {
+        let doc = fields::to_value(source)?;
+        return Ok((doc.clone(), Source::Doc(doc)));
+    }
+    let path: std::path::PathBuf = source.extract()?;
+    let path = path.to_string_lossy().to_string();
+    Ok((read(&path)?, Source::File(path)))
+}
+
+/// Records a successfully applied source for `reload()`.
+pub fn remember(source: Source) {
+    *LAST.lock().unwrap() = Some(source);
+}
+
+/// Re-reads the last configuration source.
+pub fn reload() -> Result<Value> {
+    match &*LAST.lock().unwrap() {
+        Some(Source::File(path)) => read(path),
+        Some(Source::Doc(doc)) => Ok(doc.clone()),
+        None => Err(Rejected::Invalid("fastlogger.configure() has not been called".into())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    // Console specs may hold Python streams, so dropping a plan links their
+    // release; none are created here.
+    #[no_mangle]
+    extern "C" fn _Py_Dealloc(_: *mut pyo3::ffi::PyObject) {
+        unreachable!("no Python object is freed in these tests")
+    }
+
+    fn rejected(doc: Value) -> String {
+        match plan(&doc) {
+            Ok(_) => panic!("accepted {}", doc),
+            Err(Rejected::Invalid(msg)) => msg.strip_prefix("fastlogger config: ").unwrap().to_string(),
+            Err(Rejected::Io(path, e)) => panic!("{}: {}", path, e),
+        }
+    }
+
+    #[test]
+    fn plan_reads_sinks_loggers_and_levels() {
+        let plan = plan(&json!({
+            "sinks": {"chat": {"type": "file", "path": "chat.log", "level": "WARNING"}, "mem": {"type": "memory"}},
+            "loggers": {"chat_service": {"level": "DEBUG", "sinks": ["chat", "mem", "chat"]}},
+            "levels": {"routes": 30},
+        }))
+        .ok()
+        .unwrap();
+        assert!(matches!(plan.sinks["chat"].kind, Kind::File(ref f) if f.path == "chat.log"));
+        assert!(plan.sinks["chat"].level == Some(LogLevel::WARNING));
+        assert!(matches!(plan.sinks["mem"].kind, Kind::Memory(1000)));
+        assert_eq!(plan.loggers, [("chat_service".to_string(), vec!["chat".to_string(), "mem".to_string()])]);
+        let levels: Vec<_> = plan.levels.iter().map(|(n, l)| (n.as_str(), l.name())).collect();
+        assert_eq!(levels, [("routes", "WARNING"), ("chat_service", "DEBUG")]);
+    }
+
+    #[test]
+    fn plan_rejects_unknown_keys() {
+        assert_eq!(rejected(json!({"sink": {}})), "sink: unknown key");
+        let doc = json!({"sinks": {"chat": {"type": "file", "path": "chat.log", "max_byte": 10}}});
+        assert_eq!(rejected(doc), "sinks.chat.max_byte: unknown key");
+        let doc = json!({"sinks": {"m": {"type": "memory"}}, "loggers": {"a": {"sinks": ["m"], "levle": "INFO"}}});
+        assert_eq!(rejected(doc), "loggers.a.levle: unknown key");
+        let doc = json!({"sinks": {"m": {"type": "pigeon"}}});
+        assert_eq!(rejected(doc), "sinks.m.type: unsupported sink type \"pigeon\"");
+        let doc = json!({"sinks": {"m": {"type": "file", "path": "a.log", "when": "fortnightly"}}});
+        assert_eq!(rejected(doc), "sinks.m.when: unknown value \"fortnightly\"");
+    }
+
+    #[test]
+    fn plan_rejects_bad_levels() {
+        assert_eq!(rejected(json!({"levels": {"routes": "LOUD"}})), "levels.routes: unknown log level \"LOUD\"");
+        let doc = json!({"sinks": {"m": {"type": "memory", "level": [1]}}});
+        assert_eq!(rejected(doc), "sinks.m.level: unknown log level [1]");
+        let doc = json!({"sinks": {"m": {"type": "memory"}}, "loggers": {"a": {"sinks": ["m"], "level": "LOUD"}}});
+        assert_eq!(rejected(doc), "loggers.a.level: unknown log level \"LOUD\"");
+    }
+
+    #[test]
+    fn plan_rejects_unknown_sink_names() {
+        let doc = json!({"sinks": {"m": {"type": "memory"}}, "loggers": {"a": {"sinks": ["m", "chat"]}}});
+        assert_eq!(rejected(doc), "loggers.a.sinks: no sink named \"chat\"");
+        let otlp = |fallback| json!({"sinks": {"m": {"type": "memory"}, "o": {"type": "otlp", "fallback": fallback}}});
+        assert_eq!(rejected(otlp("missing")), "sinks.o.fallback: no file sink named \"missing\"");
+        assert_eq!(rejected(otlp("m")), "sinks.o.fallback: no file sink named \"m\"");
+        let doc = json!({
+            "sinks": {"f": {"type": "file", "path": "f.log"}, "o": {"type": "otlp", "fallback": "f"}},
+            "loggers": {"a": {"sinks": ["o", "f"]}},
+        });
+        assert_eq!(rejected(doc), "loggers.a.sinks: \"f\" is the fallback of \"o\"; list only \"o\"");
+    }
+
+    #[test]
+    fn plan_rejects_wrong_types() {
+        assert_eq!(rejected(json!({"sinks": []})), "sinks: expected a table");
+        let file = |k: &str, v: Value| json!({"sinks": {"f": {"type": "file", "path": "f.log", k: v}}});
+        assert_eq!(rejected(file("max_bytes", json!("10MB"))), "sinks.f.max_bytes: expected a non-negative integer");
+        assert_eq!(rejected(file("backup_count", json!(-1))), "sinks.f.backup_count: expected a non-negative integer");
+        assert_eq!(rejected(file("background", json!("yes"))), "sinks.f.background: expected true or false");
+        let msg = "sinks.f.max_age_days: expected a non-negative number of days";
+        assert_eq!(rejected(file("max_age_days", json!(-1))), msg);
+        assert_eq!(rejected(file("path", json!(1))), "sinks.f.path: expected a string");
+        let doc = json!({"sinks": {"m": {"type": "memory"}}, "loggers": {"a": {"sinks": "m"}}});
+        assert_eq!(rejected(doc), "loggers.a.sinks: expected a non-empty list of sink names");
+        let doc = json!({"sinks": {"m": {"type": "memory"}}, "loggers": {"a": {"sinks": [1]}}});
+        assert_eq!(rejected(doc), "loggers.a.sinks: expected a list of sink names");
+    }
+
+    #[test]
+    fn reload_rereads_the_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("logging.toml");
+        std::fs::write(&path, "[levels]\nroutes = \"INFO\"\n").unwrap();
+        let path = path.to_string_lossy().to_string();
+        remember(Source::File(path.clone()));
+        assert_eq!(reload().ok().unwrap(), json!({"levels": {"routes": "INFO"}}));
+        std::fs::write(&path, "[levels]\nroutes = \"ERROR\"\n").unwrap();
+        assert_eq!(reload().ok().unwrap(), json!({"levels": {"routes": "ERROR"}}));
+        std::fs::write(&path, "[levels\n").unwrap();
+        let at = format!("fastlogger config: {}:", path);
+        assert!(matches!(reload(), Err(Rejected::Invalid(msg)) if msg.starts_with(&at)));
+    }
+}
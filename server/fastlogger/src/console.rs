@@ -0,0 +1,102 @@
+use pyo3::prelude::*;
+
+use crate::level::LogLevel;
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Color {
+    Auto,
+    Always,
+    Never,
+}
+
+impl Color {
+    pub fn parse(s: &str) -> Option<Self> {
+        match s.to_lowercase().as_str() {
+            "auto" => Some(Color::Auto),
+            "always" => Some(Color::Always),
+            "never" => Some(Color::Never),
+            _ => None,
+        }
+    }
+}
+
+fn ansi(level: LogLevel) -> &'static str {
+    match level {
+        LogLevel::TRACE => "\x1b[2m",
+        LogLevel::DEBUG => "\x1b[36m",
+        LogLevel::INFO => "\x1b[32m",
+        LogLevel::WARNING => "\x1b[33m",
+        LogLevel::ERROR => "\x1b[31m",
+        LogLevel::CRITICAL => "\x1b[1;31m",
+    }
+}
+
+/// Writes `text` to the current `sys.stderr`, or to the process's stderr
+/// when Python cannot take it, e.g. while it shuts down.
+pub fn to_stderr(text: &str) {
+    let written = unsafe { pyo3::ffi::Py_IsInitialized() } != 0
+        && Python::with_gil(|py| {
+            let stream = py.import("sys")?.getattr("stderr")?;
+            if !stream.is_none() {
+                stream.call_method1("write", (text,))?;
+            }
+            PyResult::Ok(())
+        })
+        .is_ok();
+    if !written {
+        eprint!("{}", text);
+    }
+}
+
+/// Echoes records to Python's streams: WARNING and up to stderr, the rest
+/// to stdout. Without explicit streams the current `sys.stdout` and
+/// `sys.stderr` are looked up on every write, so capture and redirection
+/// keep working.
+pub struct Console {
+    pub stdout: Option<Py<PyAny>>,
+    pub stderr: Option<Py<PyAny>>,
+    pub color: Color,
+}
+
+impl Console {
+    pub fn new(color: Color) -> Self {
+        Console { stdout: None, stderr: None, color }
+    }
+
+    fn stream<'py>(&self, py: Python<'py>, level: LogLevel) -> PyResult<Bound<'py, PyAny>> {
+        let (own, name) = if level >= LogLevel::WARNING { (&self.stderr, "stderr") } else { (&self.stdout, "stdout") };
+        match own {
+            Some(s) => Ok(s.bind(py).clone()),
+            None => py.import("sys")?.getattr(name),
+        }
+    }
+
+    fn colored(&self, stream: &Bound<'_, PyAny>) -> bool {
+        match self.color {
+            Color::Always => true,
+            Color::Never => false,
+            Color::Auto => {
+                // https://no-color.org: any non-empty value turns colour off.
+                if std::env::var_os("NO_COLOR").is_some_and(|v| !v.is_empty()) {
+                    return false;
+                }
+                stream.call_method0("isatty").and_then(|r| r.is_truthy()).unwrap_or(false)
+            }
+        }
+    }
+
+    pub fn write(&self, py: Python<'_>, level: LogLevel, line: &str) -> PyResult<()> {
+        let stream = self.stream(py, level)?;
+        if stream.is_none() {
+            // sys.stdout is None under pythonw and some daemons.
+            return Ok(());
+        }
+        if self.colored(&stream) {
+            let body = line.strip_suffix('\n').unwrap_or(line);
+            stream.call_method1("write", (format!("{}{}\x1b[0m\n", ansi(level), body),))?;
+        } else {
+            stream.call_method1("write", (line,))?;
+        }
+        Ok(())
+    }
+}
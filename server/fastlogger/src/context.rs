@@ -0,0 +1,45 @@
+use pyo3::prelude::*;
+use pyo3::sync::GILOnceCell;
+use pyo3::types::PyDict;
+
+use crate::fields::{self, Fields};
+
+/// Source of `fastlogger.context`. The fields live in a `ContextVar`, so
+/// they follow a request across await points and into tasks it starts.
+const CONTEXT_PY: &str = r#"
+import contextlib
+import contextvars
+
+fields = contextvars.ContextVar("fastlogger_context", default=None)
+
+
+@contextlib.contextmanager
+def context(**kwargs):
+    """Adds ``kwargs`` to every record logged inside the block."""
+    current = fields.get()
+    token = fields.set({**current, **kwargs} if current else kwargs)
+    try:
+        yield
+    finally:
+        fields.reset(token)
+"#;
+
+static FIELDS: GILOnceCell<Py<PyAny>> = GILOnceCell::new();
+
+pub fn install(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    let py = m.py();
+    let code = std::ffi::CString::new(CONTEXT_PY)?;
+    let module = PyModule::from_code(py, &code, c"fastlogger/context.py", c"fastlogger._context")?;
+    FIELDS.get_or_init(py, || module.getattr("fields").unwrap().unbind());
+    m.add("context", module.getattr("context")?)
+}
+
+/// Fields set by the enclosing `fastlogger.context(...)` blocks.
+pub fn current(py: Python<'_>) -> PyResult<Fields> {
+    let Some(var) = FIELDS.get(py) else { return Ok(Fields::new()) };
+    let value = var.bind(py).call_method0("get")?;
+    if value.is_none() {
+        return Ok(Fields::new());
+    }
+    fields::from_kwargs(Some(value.downcast::<PyDict>()?))
+}
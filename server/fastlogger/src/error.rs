@@ -0,0 +1,140 @@
+use pyo3::create_exception;
+use pyo3::exceptions::PyOSError;
+use pyo3::prelude::*;
+use std::io;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Mutex, OnceLock};
+
+create_exception!(fastlogger, LogError, PyOSError, "Raised when a log file cannot be opened or written.");
+
+/// What happens when writing a record fails after construction.
+#[derive(Clone, Copy, PartialEq)]
+pub enum OnError {
+    Raise,
+    Ignore,
+    Stderr,
+}
+
+impl OnError {
+    pub fn parse(s: &str) -> Option<Self> {
+        match s.to_lowercase().as_str() {
+            "raise" => Some(OnError::Raise),
+            "ignore" => Some(OnError::Ignore),
+            "stderr" => Some(OnError::Stderr),
+            _ => None,
+        }
+    }
+}
+
+/// Where the "stderr" policy writes; the module points it at Python's
+/// `sys.stderr` when it loads.
+pub static STDERR: OnceLock<fn(&str)> = OnceLock::new();
+
+fn report(msg: &str) {
+    match STDERR.get() {
+        Some(write) => write(msg),
+        None => eprint!("{}", msg),
+    }
+}
+
+/// Applies the `on_error` policy and counts failures it swallowed.
+pub struct Errors {
+    path: String,
+    policy: Mutex<OnError>,
+    suppressed: AtomicU64,
+    /// Failure from the background thread, raised on the caller's next call.
+    pending: Mutex<Option<io::Error>>,
+    /// Background failures under the "stderr" policy, written on the caller's
+    /// next call: a background thread must not wait for Python, which may be
+    /// shutting down.
+    reports: Mutex<Vec<String>>,
+}
+
+impl Errors {
+    pub fn new(path: &str, policy: OnError) -> Self {
+        Errors {
+            path: path.to_string(),
+            policy: Mutex::new(policy),
+            suppressed: AtomicU64::new(0),
+            pending: Mutex::new(None),
+            reports: Mutex::new(Vec::new()),
+        }
+    }
+
+    fn message(&self, e: &io::Error) -> String {
+        format!("fastlogger: {}: {}\n", self.path, e)
+    }
+
+    pub fn handle(&self, e: io::Error) -> io::Result<()> {
+        let policy = *self.policy.lock().unwrap();
+        match policy {
+            OnError::Raise => return Err(e),
+            OnError::Ignore => {}
+            OnError::Stderr => report(&self.message(&e)),
+        }
+        self.suppressed.fetch_add(1, Ordering::Relaxed);
+        Ok(())
+    }
+
+    /// Like `handle`, for failures on a thread that cannot return them.
+    pub fn defer(&self, e: io::Error) {
+        let policy = *self.policy.lock().unwrap();
+        match policy {
+            OnError::Raise => {
+                self.pending.lock().unwrap().get_or_insert(e);
+            }
+            OnError::Ignore => {
+                self.suppressed.fetch_add(1, Ordering::Relaxed);
+            }
+            OnError::Stderr => {
+                self.reports.lock().unwrap().push(self.message(&e));
+                self.suppressed.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+    }
+
+    pub fn set_policy(&self, policy: OnError) {
+        *self.policy.lock().unwrap() = policy;
+    }
+
+    /// Writes deferred reports and returns a deferred failure, if any.
+    pub fn take_pending(&self) -> io::Result<()> {
+        let reports = std::mem::take(&mut *self.reports.lock().unwrap());
+        for msg in &reports {
+            report(msg);
+        }
+        match self.pending.lock().unwrap().take() {
+            Some(e) => Err(e),
+            None => Ok(()),
+        }
+    }
+
+    pub fn to_py(&self, e: io::Error) -> PyErr {
+        to_py(&self.path, e)
+    }
+
+    pub fn suppressed(&self) -> u64 {
+        self.suppressed.load(Ordering::Relaxed)
+    }
+}
+
+impl Drop for Errors {
+    /// Reports nobody collected go straight to the process's stderr.
+    fn drop(&mut self) {
+        for msg in self.reports.get_mut().unwrap().drain(..) {
+            eprint!("{}", msg);
+        }
+    }
+}
+
+/// Converts an I/O failure on `path` into a `LogError` carrying errno and filename.
+pub fn to_py(path: &str, e: io::Error) -> PyErr {
+    match e.raw_os_error() {
+        Some(code) => {
+            let msg = e.to_string();
+            let msg = msg.trim_end_matches(&format!(" (os error {})", code)).to_string();
+            LogError::new_err((code, msg, path.to_string()))
+        }
+        None => LogError::new_err(format!("{}: {}", path, e)),
+    }
+}
@@ -0,0 +1,166 @@
+use pyo3::exceptions::{PyBaseException, PyTypeError};
+use pyo3::intern;
+use pyo3::prelude::*;
+use serde_json::{json, Map, Value};
+
+use crate::format::Code;
+
+pub struct Frame {
+    file: String,
+    line: Option<i64>,
+    function: String,
+}
+
+#[derive(Clone, Copy)]
+enum Link {
+    /// `raise ... from ...`
+    Cause,
+    /// Raised while handling another exception.
+    Context,
+}
+
+/// An exception and the chain of exceptions that led to it.
+pub struct ExcInfo {
+    type_name: String,
+    message: String,
+    frames: Vec<Frame>,
+    origin: Option<(Link, Box<ExcInfo>)>,
+}
+
+/// The exception being handled by the current `except` block, if any.
+pub fn active(py: Python<'_>) -> PyResult<Option<Bound<'_, PyAny>>> {
+    let exc = py.import("sys")?.call_method0("exc_info")?.get_item(1)?;
+    Ok((!exc.is_none()).then_some(exc))
+}
+
+/// The Python code that called into the logger; native methods add no
+/// frame of their own.
+pub fn caller(py: Python<'_>) -> PyResult<Option<Code>> {
+    let Ok(frame) = py.import("sys")?.call_method1("_getframe", (0,)) else { return Ok(None) };
+    let code = frame.getattr(intern!(py, "f_code"))?;
+    Ok(Some(Code {
+        file: code.getattr(intern!(py, "co_filename"))?.extract()?,
+        line: frame.getattr(intern!(py, "f_lineno"))?.extract()?,
+        func: code.getattr(intern!(py, "co_name"))?.extract()?,
+    }))
+}
+
+fn type_name(exc: &Bound<'_, PyAny>) -> PyResult<String> {
+    let py = exc.py();
+    let ty = exc.get_type();
+    let qualname: String = ty.getattr(intern!(py, "__qualname__"))?.extract()?;
+    let module: String = ty.getattr(intern!(py, "__module__"))?.extract()?;
+    Ok(match module.as_str() {
+        "builtins" | "__main__" => qualname,
+        _ => format!("{}.{}", module, qualname),
+    })
+}
+
+fn frames(exc: &Bound<'_, PyAny>) -> PyResult<Vec<Frame>> {
+    let py = exc.py();
+    let mut out = Vec::new();
+    let mut tb = exc.getattr(intern!(py, "__traceback__"))?;
+    while !tb.is_none() {
+        let code = tb.getattr(intern!(py, "tb_frame"))?.getattr(intern!(py, "f_code"))?;
+        out.push(Frame {
+            file: code.getattr(intern!(py, "co_filename"))?.extract()?,
+            line: tb.getattr(intern!(py, "tb_lineno"))?.extract()?,
+            function: code.getattr(intern!(py, "co_name"))?.extract()?,
+        });
+        tb = tb.getattr(intern!(py, "tb_next"))?;
+    }
+    Ok(out)
+}
+
+fn origin<'py>(exc: &Bound<'py, PyAny>) -> PyResult<Option<(Link, Bound<'py, PyAny>)>> {
+    let py = exc.py();
+    let cause = exc.getattr(intern!(py, "__cause__"))?;
+    if !cause.is_none() {
+        return Ok(Some((Link::Cause, cause)));
+    }
+    let context = exc.getattr(intern!(py, "__context__"))?;
+    if context.is_none() || exc.getattr(intern!(py, "__suppress_context__"))?.is_truthy()? {
+        return Ok(None);
+    }
+    Ok(Some((Link::Context, context)))
+}
+
+/// Captures `exc` with its `__cause__`/`__context__` chain.
+pub fn capture(exc: &Bound<'_, PyAny>) -> PyResult<ExcInfo> {
+    if !exc.is_instance_of::<PyBaseException>() {
+        return Err(PyTypeError::new_err(format!("exc must be an exception, not {}", exc.get_type().name()?)));
+    }
+    // Walk the chain first; an exception can end up in its own context.
+    let mut chain = vec![(None, exc.clone())];
+    while let Some((link, next)) = origin(&chain[chain.len() - 1].1)? {
+        if chain.iter().any(|(_, e)| e.is(&next)) {
+            break;
+        }
+        chain.push((Some(link), next));
+    }
+    let mut info: Option<ExcInfo> = None;
+    let mut link = None;
+    for (l, e) in chain.into_iter().rev() {
+        info = Some(ExcInfo {
+            type_name: type_name(&e)?,
+            message: e.str()?.to_string(),
+            frames: frames(&e)?,
+            origin: link.zip(info.map(Box::new)),
+        });
+        link = l;
+    }
+    Ok(info.unwrap())
+}
+
+impl ExcInfo {
+    pub fn type_name(&self) -> &str {
+        &self.type_name
+    }
+
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+
+    pub fn to_value(&self) -> Value {
+        let frames: Vec<Value> = self
+            .frames
+            .iter()
+            .map(|f| json!({ "file": f.file, "line": f.line, "function": f.function }))
+            .collect();
+        let mut obj = Map::new();
+        obj.insert("type".into(), json!(self.type_name));
+        obj.insert("message".into(), json!(self.message));
+        obj.insert("frames".into(), Value::Array(frames));
+        match &self.origin {
+            Some((Link::Cause, e)) => obj.insert("cause".into(), e.to_value()),
+            Some((Link::Context, e)) => obj.insert("context".into(), e.to_value()),
+            None => None,
+        };
+        Value::Object(obj)
+    }
+
+    /// Appends the chain the way Python prints it, innermost exception first.
+    pub fn render_text(&self, out: &mut String) {
+        if let Some((link, e)) = &self.origin {
+            e.render_text(out);
+            out.push_str(match link {
+                Link::Cause => "\nThe above exception was the direct cause of the following exception:\n\n",
+                Link::Context => "\nDuring handling of the above exception, another exception occurred:\n\n",
+            });
+        }
+        if !self.frames.is_empty() {
+            out.push_str("Traceback (most recent call last):\n");
+        }
+        for f in &self.frames {
+            match f.line {
+                Some(n) => out.push_str(&format!("  File \"{}\", line {}, in {}\n", f.file, n, f.function)),
+                None => out.push_str(&format!("  File \"{}\", in {}\n", f.file, f.function)),
+            }
+        }
+        if self.message.is_empty() {
+            out.push_str(&format!("{}\n", self.type_name));
+        } else {
+            out.push_str(&format!("{}: {}\n", self.type_name, self.message));
+        }
+    }
+}
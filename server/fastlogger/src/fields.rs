@@ -0,0 +1,149 @@
+use pyo3::prelude::*;
+use pyo3::types::{PyBool, PyDict, PyFloat, PyInt, PyList, PyString, PyTuple};
+use serde_json::{Map, Value};
+
+pub type Fields = Vec<(String, Value)>;
+
+pub fn to_value(obj: &Bound<'_, PyAny>) -> PyResult<Value> {
+    if obj.is_none() {
+        return Ok(Value::Null);
+    }
+    // bool must be checked before int: Python's bool is an int subclass.
+    if let Ok(b) = obj.downcast::<PyBool>() {
+        return Ok(Value::Bool(b.is_true()));
+    }
+    if obj.is_instance_of::<PyInt>() {
+        if let Ok(i) = obj.extract::<i64>() {
+            return Ok(Value::from(i));
+        }
+    }
+    if let Ok(f) = obj.downcast::<PyFloat>() {
+        return Ok(Value::from(f.value()));
+    }
+    if let Ok(s) = obj.downcast::<PyString>() {
+        return Ok(Value::String(s.to_str()?.to_string()));
+    }
+    if let Ok(d) = obj.downcast::<PyDict>() {
+        let mut m = Map::new();
+        for (k, v) in d.iter() {
+            m.insert(k.str()?.to_string(), to_value(&v)?);
+        }
+        return Ok(Value::Object(m));
+    }
+    if obj.is_instance_of::<PyList>() || obj.is_instance_of::<PyTuple>() {
+        let items = obj.try_iter()?.map(|i| to_value(&i?)).collect::<PyResult<Vec<_>>>()?;
+        return Ok(Value::Array(items));
+    }
+    Ok(Value::String(obj.str()?.to_string()))
+}
+
+/// The inverse of `to_value`.
+pub fn to_python<'py>(py: Python<'py>, v: &Value) -> PyResult<Bound<'py, PyAny>> {
+    Ok(match v {
+        Value::Null => py.None().into_bound(py),
+        Value::Bool(b) => PyBool::new(py, *b).to_owned().into_any(),
+        Value::Number(n) => match (n.as_i64(), n.as_u64()) {
+            (Some(i), _) => i.into_pyobject(py)?.into_any(),
+            (None, Some(u)) => u.into_pyobject(py)?.into_any(),
+            _ => n.as_f64().unwrap_or(f64::NAN).into_pyobject(py)?.into_any(),
+        },
+        Value::String(s) => PyString::new(py, s).into_any(),
+        Value::Array(a) => {
+            let items = a.iter().map(|v| to_python(py, v)).collect::<PyResult<Vec<_>>>()?;
+            PyList::new(py, items)?.into_any()
+        }
+        Value::Object(o) => {
+            let d = PyDict::new(py);
+            for (k, v) in o {
+                d.set_item(k, to_python(py, v)?)?;
+            }
+            d.into_any()
+        }
+    })
+}
+
+pub fn from_kwargs(kwargs: Option<&Bound<'_, PyDict>>) -> PyResult<Fields> {
+    let mut fields = Fields::new();
+    if let Some(d) = kwargs {
+        for (k, v) in d.iter() {
+            fields.push((k.extract::<String>()?, to_value(&v)?));
+        }
+    }
+    Ok(fields)
+}
+
+/// Adds `extra` to `fields`, replacing values of keys already present.
+pub fn merge(fields: &mut Fields, extra: impl IntoIterator<Item = (String, Value)>) {
+    for (k, v) in extra {
+        match fields.iter_mut().find(|(key, _)| *key == k) {
+            Some(slot) => slot.1 = v,
+            None => fields.push((k, v)),
+        }
+    }
+}
+
+/// `s` bare if logfmt can take it so, else as a quoted JSON string.
+fn logfmt_str(s: &str) -> String {
+    if s.is_empty() || s.chars().any(|c| c.is_whitespace() || c.is_control() || c == '"' || c == '=') {
+        Value::String(s.to_string()).to_string()
+    } else {
+        s.to_string()
+    }
+}
+
+/// Renders fields as logfmt-style `key=value` pairs. Keys and string values
+/// with spaces, quotes, `=` or control characters are quoted.
+pub fn render_text(fields: &Fields) -> String {
+    let mut out = String::new();
+    for (k, v) in fields {
+        if !out.is_empty() {
+            out.push(' ');
+        }
+        let v = match v {
+            Value::String(s) => logfmt_str(s),
+            other => other.to_string(),
+        };
+        out.push_str(&format!("{}={}", logfmt_str(k), v));
+    }
+    out
+}
+
+pub fn to_object(fields: &Fields) -> Value {
+    Value::Object(fields.iter().cloned().collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn fields(pairs: &[(&str, Value)]) -> Fields {
+        pairs.iter().map(|(k, v)| (k.to_string(), v.clone())).collect()
+    }
+
+    #[test]
+    fn logfmt_leaves_plain_pairs_bare() {
+        let f = fields(&[("user", json!("ana")), ("n", json!(3)), ("ok", json!(true)), ("tags", json!(["a", 1]))]);
+        assert_eq!(render_text(&f), r#"user=ana n=3 ok=true tags=["a",1]"#);
+        assert_eq!(render_text(&fields(&[("städte", json!("Zürich"))])), "städte=Zürich");
+    }
+
+    #[test]
+    fn logfmt_quotes_values_that_would_split() {
+        let f = fields(&[
+            ("a", json!("two words")),
+            ("b", json!("k=v")),
+            ("c", json!("say \"hi\"")),
+            ("d", json!("line\nbreak")),
+            ("e", json!("")),
+            ("f", json!("bell\u{7}")),
+        ]);
+        assert_eq!(render_text(&f), r#"a="two words" b="k=v" c="say \"hi\"" d="line\nbreak" e="" f="bell\u0007""#);
+    }
+
+    #[test]
+    fn logfmt_quotes_keys_that_would_split() {
+        let f = fields(&[("a b", json!(1)), ("x=y", json!(2)), ("", json!(3)), ("q\"", json!(4))]);
+        assert_eq!(render_text(&f), r#""a b"=1 "x=y"=2 ""=3 "q\""=4"#);
+    }
+}
@@ -0,0 +1,262 @@
+use chrono::NaiveDateTime;
+use std::fs::{File, OpenOptions};
+use std::io::{self, BufWriter, Write};
+use std::os::unix::fs::MetadataExt;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use crate::compress;
+use crate::lock::FileLock;
+use crate::rotation::{self, Interval, Settings};
+
+/// How often an open handle is checked against the path it was opened from.
+const REOPEN_CHECK: Duration = Duration::from_secs(1);
+
+struct OpenFile {
+    out: BufWriter<File>,
+    ino: u64,
+    dev: u64,
+    size: u64,
+    checked: Instant,
+    period: Option<NaiveDateTime>,
+}
+
+impl OpenFile {
+    fn open(path: &str, interval: Option<Interval>) -> io::Result<Self> {
+        let f = OpenOptions::new().create(true).append(true).open(path)?;
+        let meta = f.metadata()?;
+        // An existing file belongs to the period it was last written in, so a
+        // restart after midnight still rolls yesterday's lines away.
+        let period = interval.map(|iv| match meta.modified() {
+            Ok(t) if meta.len() > 0 => rotation::period_of(iv, t),
+            _ => iv.start(rotation::now()),
+        });
+        Ok(OpenFile {
+            ino: meta.ino(),
+            dev: meta.dev(),
+            size: meta.len(),
+            out: BufWriter::new(f),
+            checked: Instant::now(),
+            period,
+        })
+    }
+
+    /// Current size of `path`, or `None` if it no longer names the file this
+    /// handle points at, e.g. after logrotate or another process moved it away.
+    fn current_size(&self, path: &str) -> Option<u64> {
+        match std::fs::metadata(path) {
+            Ok(m) if m.ino() == self.ino && m.dev() == self.dev => Some(m.len()),
+            _ => None,
+        }
+    }
+}
+
+pub struct FileTarget {
+    path: String,
+    rotation: Settings,
+    file: Option<OpenFile>,
+    /// Held while backups are renamed, always after the sidecar lock.
+    backups: Arc<Mutex<()>>,
+}
+
+impl FileTarget {
+    /// Opens `path` straight away so a bad location fails at construction.
+    pub fn new(path: String, rotation: Settings) -> io::Result<Self> {
+        let mut target = FileTarget { path, rotation, file: None, backups: Arc::new(Mutex::new(())) };
+        target.handle()?;
+        Ok(target)
+    }
+
+    fn handle(&mut self) -> io::Result<&mut OpenFile> {
+        let stale = match &mut self.file {
+            Some(f) if f.checked.elapsed() >= REOPEN_CHECK => {
+                f.checked = Instant::now();
+                // Other processes append to the same file, so pick up
+                // their bytes as well as ours.
+                match f.current_size(&self.path) {
+                    Some(size) => {
+                        f.size = f.size.max(size);
+                        false
+                    }
+                    None => true,
+                }
+            }
+            Some(_) => false,
+            None => true,
+        };
+        if stale {
+            self.close()?;
+            self.file = Some(OpenFile::open(&self.path, self.rotation.policy.interval)?);
+        }
+        Ok(self.file.as_mut().unwrap())
+    }
+
+    fn close(&mut self) -> io::Result<()> {
+        match self.file.take() {
+            Some(mut f) => f.out.flush(),
+            None => Ok(()),
+        }
+    }
+
+    /// Rolls the current file over if the policy says it is due.
+    /// Rotates before a write of `incoming` bytes would push a non-empty
+    /// file past `max_bytes`, or when the period has ended.
+    fn maybe_rotate(&mut self, incoming: u64) -> io::Result<()> {
+        let Settings { max_bytes, policy, .. } = self.rotation;
+        let f = self.handle()?;
+        let mut dated = None;
+        if let (Some(iv), Some(period)) = (policy.interval, f.period) {
+            let current = iv.start(rotation::now());
+            if current != period {
+                if f.size == 0 {
+                    f.period = Some(current);
+                } else {
+                    dated = Some(iv.suffix(period));
+                }
+            }
+        }
+        let sized = policy.size && f.size > 0 && f.size + incoming > max_bytes;
+        if dated.is_none() && !sized {
+            return Ok(());
+        }
+        // Without the lock another process could rotate the same file at
+        // once; skip this rotation and report it rather than risk that.
+        let _flock = FileLock::acquire(&self.path)?;
+        // Another process may have rotated while we waited for the lock; if
+        // so the path is already a fresh file and we only need to reopen it.
+        let moved = self.file.as_ref().is_some_and(|f| f.current_size(&self.path).is_none());
+        self.close()?;
+        if moved {
+            return Ok(());
+        }
+        let count = self.rotation.backup_count;
+        {
+            let _guard = self.backups.lock().unwrap();
+            match dated {
+                Some(suffix) => {
+                    rotation::rotate_dated(&self.path, &suffix)?;
+                    rotation::prune_dated(&self.path, count);
+                }
+                None => rotation::rotate_numbered(&self.path, count)?,
+            }
+            if let Some(age) = self.rotation.max_age {
+                rotation::prune_aged(&self.path, age);
+            }
+        }
+        if let Some(codec) = self.rotation.compression {
+            compress::schedule(&self.path, codec, self.backups.clone());
+        }
+        Ok(())
+    }
+
+    /// Appends `line`. A failed rotation is reported, but the line is still
+    /// written to the current file rather than lost.
+    pub fn write(&mut self, line: &str) -> io::Result<()> {
+        let rotated = self.maybe_rotate(line.len() as u64);
+        let f = self.handle()?;
+        f.out.write_all(line.as_bytes())?;
+        f.size += line.len() as u64;
+        rotated
+    }
+
+    pub fn flush(&mut self) -> io::Result<()> {
+        match &mut self.file {
+            Some(f) => f.out.flush(),
+            None => Ok(()),
+        }
+    }
+}
+
+impl Drop for FileTarget {
+    fn drop(&mut self) {
+        self.close().ok();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rotation::Policy;
+    use std::collections::HashSet;
+    use std::process::Command;
+
+    const WORKERS: usize = 8;
+    const LINES: usize = 2000;
+    const CHILD_ENV: &str = "FASTLOGGER_HAMMER_PATH";
+
+    fn settings() -> Settings {
+        Settings {
+            max_bytes: 4096,
+            backup_count: 1000,
+            policy: Policy { size: true, interval: None },
+            max_age: None,
+            compression: None,
+        }
+    }
+
+    /// Runs inside each child process spawned by `rotation_across_processes`.
+    #[test]
+    #[ignore]
+    fn hammer_child() {
+        let path = match std::env::var(CHILD_ENV) {
+            Ok(p) => p,
+            Err(_) => return,
+        };
+        let mut target = FileTarget::new(path, settings()).unwrap();
+        let pid = std::process::id();
+        for i in 0..LINES {
+            target.write(&format!("{} {:06} padding-padding-padding\n", pid, i)).unwrap();
+            target.flush().unwrap();
+        }
+    }
+
+    #[test]
+    fn rotation_across_processes() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("hammer.log");
+        let exe = std::env::current_exe().unwrap();
+        let children: Vec<_> = (0..WORKERS)
+            .map(|_| {
+                Command::new(&exe)
+                    .args(["file::tests::hammer_child", "--exact", "--ignored", "--quiet"])
+                    .env(CHILD_ENV, &path)
+                    .spawn()
+                    .unwrap()
+            })
+            .collect();
+        for mut c in children {
+            assert!(c.wait().unwrap().success());
+        }
+
+        let mut seen = HashSet::new();
+        let mut files = 0;
+        for entry in std::fs::read_dir(dir.path()).unwrap().flatten() {
+            let name = entry.file_name().into_string().unwrap();
+            if !name.starts_with("hammer.log") {
+                continue;
+            }
+            files += 1;
+            for line in std::fs::read_to_string(entry.path()).unwrap().lines() {
+                assert!(line.ends_with("padding"), "torn line {:?} in {}", line, name);
+                assert!(seen.insert(line.to_string()), "duplicate line {:?}", line);
+            }
+        }
+        assert!(files > 2, "expected several rotations, got {} files", files);
+        assert_eq!(seen.len(), WORKERS * LINES);
+    }
+
+    #[test]
+    fn no_rotation_without_lock() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("locked.log");
+        // A directory where the lock file belongs makes locking fail.
+        std::fs::create_dir(dir.path().join(".locked.log.lock")).unwrap();
+        let mut target = FileTarget::new(path.to_string_lossy().into(), settings()).unwrap();
+        let line = format!("{}\n", "x".repeat(3000));
+        target.write(&line).unwrap();
+        assert!(target.write(&line).is_err());
+        target.flush().unwrap();
+        assert!(!dir.path().join("locked.log.1").exists());
+        assert_eq!(std::fs::read_to_string(&path).unwrap().len(), 2 * line.len());
+    }
+}
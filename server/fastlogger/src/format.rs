@@ -0,0 +1,248 @@
+use chrono::{DateTime, FixedOffset, Utc};
+use serde_json::{json, Map, Value};
+use std::borrow::Cow;
+use std::sync::Arc;
+
+use crate::clock::Clock;
+use crate::exception::ExcInfo;
+use crate::fields::{self, Fields};
+use crate::template::Template;
+
+#[derive(Clone)]
+pub enum Format {
+    Text,
+    Json,
+    Template(Arc<Template>),
+}
+
+impl Format {
+    pub fn parse(s: &str) -> Option<Self> {
+        match s.to_lowercase().as_str() {
+            "text" => Some(Format::Text),
+            "json" => Some(Format::Json),
+            _ => None,
+        }
+    }
+
+    /// Applies a `fmt=` line template on top of the text format.
+    pub fn with_template(self, fmt: Option<&str>) -> Result<Self, String> {
+        match (self, fmt) {
+            (f, None) => Ok(f),
+            (Format::Json, Some(_)) => Err("fmt only applies to the text format".into()),
+            (_, Some(t)) => Ok(Format::Template(Arc::new(Template::compile(t)?))),
+        }
+    }
+}
+
+/// How text output keeps one record per line when a message or traceback
+/// spans several.
+#[derive(Clone, Copy, PartialEq)]
+pub enum Newlines {
+    /// Continuation lines start with `CONTINUATION`.
+    Indent,
+    /// Newlines become a literal `\n`, and backslashes are doubled so the
+    /// two can be told apart.
+    Escape,
+    /// Left alone, for targets that carry a record as one message anyway.
+    Raw,
+}
+
+impl Newlines {
+    pub fn parse(s: &str) -> Option<Self> {
+        match s.to_lowercase().as_str() {
+            "indent" => Some(Newlines::Indent),
+            "escape" => Some(Newlines::Escape),
+            "raw" => Some(Newlines::Raw),
+            _ => None,
+        }
+    }
+
+    fn apply(self, line: &str) -> String {
+        let line = line.strip_suffix('\n').unwrap_or(line);
+        let body = || line.replace("\r\n", "\n").replace('\r', "\n");
+        let mut out = match self {
+            Newlines::Indent => body().trim_end_matches('\n').replace('\n', &format!("\n{}", CONTINUATION)),
+            Newlines::Escape => body().replace('\\', "\\\\").replace('\n', "\\n"),
+            Newlines::Raw => line.to_string(),
+        };
+        out.push('\n');
+        out
+    }
+}
+
+pub const CONTINUATION: &str = "    | ";
+
+/// Cuts `msg` to at most `limit` bytes on a character boundary and says how
+/// much was dropped.
+pub fn truncate(msg: &str, limit: Option<usize>) -> Cow<'_, str> {
+    let Some(limit) = limit.filter(|&l| msg.len() > l) else { return Cow::Borrowed(msg) };
+    let mut end = limit;
+    while !msg.is_char_boundary(end) {
+        end -= 1;
+    }
+    Cow::Owned(format!("{}\u{2026}[truncated {} bytes]", &msg[..end], msg.len() - end))
+}
+
+/// Where a record was logged from.
+pub struct Code {
+    pub file: String,
+    pub line: i64,
+    pub func: String,
+}
+
+/// What a caller logs; the formatter adds the timestamp.
+pub struct Entry<'a> {
+    pub level: &'a str,
+    pub name: &'a str,
+    pub msg: &'a str,
+    pub fields: &'a Fields,
+    pub exc: Option<&'a ExcInfo>,
+    /// Only looked up when a sink records it.
+    pub code: Option<&'a Code>,
+}
+
+pub struct Record<'a> {
+    pub time: &'a DateTime<FixedOffset>,
+    pub ts: &'a str,
+    pub level: &'a str,
+    pub name: &'a str,
+    pub msg: &'a str,
+    pub fields: &'a Fields,
+    pub exc: Option<&'a ExcInfo>,
+}
+
+/// The calling thread's OS id, as `threading.get_native_id()` reports it.
+pub fn thread_id() -> i32 {
+    unsafe { libc::gettid() }
+}
+
+fn render_json(r: &Record) -> String {
+    let mut obj = Map::new();
+    obj.insert("timestamp".into(), json!(r.ts));
+    obj.insert("level".into(), json!(r.level));
+    obj.insert("logger".into(), json!(r.name));
+    obj.insert("message".into(), json!(r.msg));
+    obj.insert("pid".into(), json!(std::process::id()));
+    obj.insert("thread".into(), json!(thread_id()));
+    if !r.fields.is_empty() {
+        obj.insert("fields".into(), fields::to_object(r.fields));
+    }
+    if let Some(e) = r.exc {
+        obj.insert("exception".into(), e.to_value());
+    }
+    let mut line = Value::Object(obj).to_string();
+    line.push('\n');
+    line
+}
+
+fn render_text(r: &Record) -> String {
+    if r.fields.is_empty() {
+        return format!("{} | {} | {} | {}\n", r.ts, r.level, r.name, r.msg);
+    }
+    format!("{} | {} | {} | {} {}\n", r.ts, r.level, r.name, r.msg, fields::render_text(r.fields))
+}
+
+/// A line format together with the clock that stamps its records.
+#[derive(Clone)]
+pub struct Formatter {
+    pub format: Format,
+    pub clock: Arc<Clock>,
+    pub newlines: Newlines,
+    pub max_message_bytes: Option<usize>,
+}
+
+impl Formatter {
+    pub fn new(format: Format, clock: Clock) -> Self {
+        Formatter { format, clock: Arc::new(clock), newlines: Newlines::Indent, max_message_bytes: None }
+    }
+
+    pub fn render(&self, now: DateTime<Utc>, e: &Entry) -> String {
+        let (time, ts) = self.clock.stamp(now);
+        let msg = truncate(e.msg, self.max_message_bytes);
+        let r = Record { time: &time, ts: &ts, level: e.level, name: e.name, msg: &msg, fields: e.fields, exc: e.exc };
+        let mut line = match &self.format {
+            Format::Text => render_text(&r),
+            Format::Json => return render_json(&r),
+            Format::Template(t) => t.render(&r),
+        };
+        if let Some(e) = r.exc {
+            e.render_text(&mut line);
+        }
+        if line.strip_suffix('\n').unwrap_or(&line).contains(['\n', '\r']) {
+            line = self.newlines.apply(&line);
+        }
+        line
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn escape_keeps_backslashes_apart_from_newlines() {
+        assert_eq!(Newlines::Escape.apply("a\nb\r\nc\n"), "a\\nb\\nc\n");
+        assert_eq!(Newlines::Escape.apply("C:\\new\\x"), "C:\\\\new\\\\x\n");
+        assert_eq!(Newlines::Escape.apply("\\\nx"), "\\\\\\nx\n");
+    }
+
+    #[test]
+    fn indent_folds_continuation_lines() {
+        assert_eq!(Newlines::Indent.apply("a\nb\n\n"), format!("a\n{}b\n", CONTINUATION));
+    }
+
+    #[test]
+    fn truncate_on_char_boundaries() {
+        assert_eq!(truncate("héllo", None), "héllo");
+        assert_eq!(truncate("héllo", Some(6)), "héllo");
+        // 'é' is bytes 1..3; a limit inside it backs off to before it.
+        assert_eq!(truncate("héllo", Some(2)), "h\u{2026}[truncated 5 bytes]");
+        assert_eq!(truncate("héllo", Some(3)), "hé\u{2026}[truncated 3 bytes]");
+        assert_eq!(truncate("日本", Some(4)), "日\u{2026}[truncated 3 bytes]");
+        assert_eq!(truncate("🦀x", Some(3)), "\u{2026}[truncated 5 bytes]");
+    }
+
+    #[test]
+    fn thread_id_is_the_os_id() {
+        let here = thread_id();
+        assert_eq!(here, thread_id());
+        assert_ne!(here, std::thread::spawn(thread_id).join().unwrap());
+        assert!(std::path::Path::new(&format!("/proc/self/task/{}", here)).exists());
+    }
+
+    fn json_line(msg: &str, fields: &Fields) -> (String, Value) {
+        let time = DateTime::parse_from_rfc3339("2026-10-16T08:30:00.250+00:00").unwrap();
+        let ts = "2026-10-16T08:30:00.250Z";
+        let r = Record { time: &time, ts, level: "INFO", name: "chat", msg, fields, exc: None };
+        let line = render_json(&r);
+        let v = serde_json::from_str(&line).unwrap();
+        (line, v)
+    }
+
+    #[test]
+    fn json_keeps_key_order() {
+        let fields = vec![("z".to_string(), json!(1)), ("a".to_string(), json!("x"))];
+        let (line, v) = json_line("hi", &fields);
+        let keys: Vec<_> = v.as_object().unwrap().keys().map(String::as_str).collect();
+        assert_eq!(keys, ["timestamp", "level", "logger", "message", "pid", "thread", "fields"]);
+        let keys: Vec<_> = v["fields"].as_object().unwrap().keys().map(String::as_str).collect();
+        assert_eq!(keys, ["z", "a"]);
+        assert_eq!(v["pid"], json!(std::process::id()));
+        assert_eq!(v["thread"], json!(thread_id()));
+        assert!(line.starts_with(r#"{"timestamp":"2026-10-16T08:30:00.250Z","level":"INFO","logger":"chat","#));
+        assert!(!json_line("hi", &Vec::new()).1.as_object().unwrap().contains_key("fields"));
+    }
+
+    #[test]
+    fn json_escapes_what_would_break_the_line() {
+        let msg = "say \"hi\" a=b\tc\nnext line \u{1b}[31m naïve 日本 🦀";
+        let fields = vec![("k\"=\n ü".to_string(), json!("v \"=\n ü"))];
+        let (line, v) = json_line(msg, &fields);
+        assert_eq!(line.matches('\n').count(), 1);
+        assert!(line.ends_with("}\n"));
+        assert!(line.contains("naïve 日本 🦀"));
+        assert!(line.contains(r#"say \"hi\" a=b\tc\nnext line \u001b[31m"#));
+        assert_eq!(v["message"], json!(msg));
+        assert_eq!(v["fields"]["k\"=\n ü"], json!("v \"=\n ü"));
+    }
+}
@@ -0,0 +1,101 @@
+use pyo3::prelude::*;
+use pyo3::types::PyDict;
+use pyo3::intern;
+use serde_json::Value;
+
+use crate::fields::{self, Fields};
+use crate::format::Code;
+
+/// Attributes every `logging.LogRecord` has; anything else came from `extra=`.
+const STANDARD_ATTRS: &[&str] = &[
+    "name", "msg", "args", "levelname", "levelno", "pathname", "filename", "module",
+    "exc_info", "exc_text", "stack_info", "lineno", "funcName", "created", "msecs",
+    "relativeCreated", "thread", "threadName", "processName", "process", "taskName",
+    "message", "asctime",
+];
+
+/// Source of `fastlogger.Handler`, a real `logging.Handler` subclass so
+/// filters, locking and `logging.shutdown()` behave as for any other handler.
+pub const HANDLER_PY: &str = r#"
+import logging
+
+
+class Handler(logging.Handler):
+    """Routes stdlib ``logging`` records into a ``fastlogger.Logger``."""
+
+    def __init__(self, logger, level=logging.NOTSET):
+        super().__init__(level)
+        self.logger = logger
+
+    def emit(self, record):
+        try:
+            self.logger.emit_record(record)
+        except Exception:
+            self.handleError(record)
+
+    def flush(self):
+        self.logger.flush()
+"#;
+
+/// A stdlib record broken into the pieces `Logger` writes.
+pub struct StdRecord {
+    pub levelno: i64,
+    pub levelname: String,
+    pub name: String,
+    pub msg: String,
+    pub fields: Fields,
+    pub code: Code,
+}
+
+fn format_exception(record: &Bound<'_, PyAny>) -> PyResult<Option<String>> {
+    let py = record.py();
+    let cached = record.getattr(intern!(py, "exc_text"))?;
+    if !cached.is_none() {
+        return Ok(Some(cached.extract()?));
+    }
+    let exc_info = record.getattr(intern!(py, "exc_info"))?;
+    if exc_info.is_none() || !exc_info.is_truthy()? {
+        return Ok(None);
+    }
+    let lines = py
+        .import("traceback")?
+        .call_method1("format_exception", exc_info.downcast::<pyo3::types::PyTuple>()?)?;
+    let text: String = py.import("builtins")?.getattr("str")?.call_method1("join", ("", lines))?.extract()?;
+    let text = text.trim_end().to_string();
+    // Cache like logging.Formatter does, so other handlers reuse it.
+    record.setattr(intern!(py, "exc_text"), &text)?;
+    Ok(Some(text))
+}
+
+pub fn from_record(record: &Bound<'_, PyAny>) -> PyResult<StdRecord> {
+    let py = record.py();
+    let mut fields = Fields::new();
+    let attrs = record.getattr(intern!(py, "__dict__"))?;
+    let extra = PyDict::new(py);
+    for (k, v) in attrs.downcast::<PyDict>()?.iter() {
+        let key: String = k.extract()?;
+        if !STANDARD_ATTRS.contains(&key.as_str()) && !key.starts_with('_') {
+            extra.set_item(key, v)?;
+        }
+    }
+    fields.extend(fields::from_kwargs(Some(&extra))?);
+    if let Some(exc) = format_exception(record)? {
+        fields.push(("exc_info".into(), Value::String(exc)));
+    }
+    let stack = record.getattr(intern!(py, "stack_info"))?;
+    if !stack.is_none() {
+        fields.push(("stack_info".into(), Value::String(stack.extract()?)));
+    }
+    Ok(StdRecord {
+        levelno: record.getattr(intern!(py, "levelno"))?.extract()?,
+        levelname: record.getattr(intern!(py, "levelname"))?.extract()?,
+        name: record.getattr(intern!(py, "name"))?.extract()?,
+        msg: record.call_method0(intern!(py, "getMessage"))?.extract()?,
+        fields,
+        code: Code {
+            file: record.getattr(intern!(py, "pathname"))?.extract()?,
+            line: record.getattr(intern!(py, "lineno"))?.extract()?,
+            func: record.getattr(intern!(py, "funcName"))?.extract()?,
+        },
+    })
+}
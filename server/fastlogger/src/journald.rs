@@ -0,0 +1,282 @@
+use serde_json::Value;
+use std::fs::File;
+use std::io::{self, Write};
+use std::mem;
+use std::os::fd::{AsRawFd, FromRawFd, RawFd};
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::net::UnixDatagram;
+use std::path::Path;
+
+use crate::error::Errors;
+use crate::fields::Fields;
+use crate::format::Code;
+use crate::level::LogLevel;
+use crate::syslog;
+
+pub const SOCKET: &str = "/run/systemd/journal/socket";
+
+/// Journal field names are upper-case ASCII, digits and `_`, may not start
+/// with `_` or a digit, and are at most 64 characters. Names that would
+/// start with either get an `F_` prefix.
+fn key(name: &str) -> Option<String> {
+    let mut k: String = name
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c.to_ascii_uppercase() } else { '_' })
+        .collect();
+    if k.starts_with(|c: char| c == '_' || c.is_ascii_digit()) {
+        k.insert_str(0, "F_");
+    }
+    (!k.is_empty()).then(|| k.chars().take(64).collect())
+}
+
+/// Appends one field; values with newlines use the length-prefixed form.
+fn field(out: &mut Vec<u8>, key: &str, value: &[u8]) {
+    out.extend_from_slice(key.as_bytes());
+    if value.contains(&b'\n') {
+        out.push(b'\n');
+        out.extend_from_slice(&(value.len() as u64).to_le_bytes());
+    } else {
+        out.push(b'=');
+    }
+    out.extend_from_slice(value);
+    out.push(b'\n');
+}
+
+/// Builds a native-protocol datagram.
+pub fn encode(
+    level: LogLevel,
+    name: &str,
+    identifier: Option<&str>,
+    message: &str,
+    code: Option<&Code>,
+    fields: &Fields,
+) -> Vec<u8> {
+    let mut out = Vec::with_capacity(256 + message.len());
+    let mut own = vec!["MESSAGE", "PRIORITY", "LOGGER", "SYSLOG_IDENTIFIER"];
+    field(&mut out, "MESSAGE", message.as_bytes());
+    field(&mut out, "PRIORITY", syslog::severity(level).to_string().as_bytes());
+    field(&mut out, "LOGGER", name.as_bytes());
+    field(&mut out, "SYSLOG_IDENTIFIER", identifier.unwrap_or(name).as_bytes());
+    if let Some(c) = code {
+        field(&mut out, "CODE_FILE", c.file.as_bytes());
+        field(&mut out, "CODE_LINE", c.line.to_string().as_bytes());
+        field(&mut out, "CODE_FUNC", c.func.as_bytes());
+        own.extend(["CODE_FILE", "CODE_LINE", "CODE_FUNC"]);
+    }
+    for (k, v) in fields {
+        // A user field must not add a second PRIORITY or MESSAGE.
+        let Some(k) = key(k).filter(|k| !own.contains(&k.as_str())) else { continue };
+        match v {
+            Value::String(s) => field(&mut out, &k, s.as_bytes()),
+            other => field(&mut out, &k, other.to_string().as_bytes()),
+        }
+    }
+    out
+}
+
+/// A sealed memfd holding `payload`, for records too large for a datagram.
+fn memfd(payload: &[u8]) -> io::Result<File> {
+    let fd = unsafe { libc::memfd_create(c"fastlogger-journal".as_ptr(), libc::MFD_CLOEXEC | libc::MFD_ALLOW_SEALING) };
+    if fd < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    let mut f = unsafe { File::from_raw_fd(fd) };
+    f.write_all(payload)?;
+    let seals = libc::F_SEAL_SHRINK | libc::F_SEAL_GROW | libc::F_SEAL_WRITE | libc::F_SEAL_SEAL;
+    if unsafe { libc::fcntl(fd, libc::F_ADD_SEALS, seals) } < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(f)
+}
+
+/// Sends an empty datagram to `path` carrying `fd` as `SCM_RIGHTS`.
+fn send_fd(sock: &UnixDatagram, path: &Path, fd: RawFd) -> io::Result<()> {
+    unsafe {
+        let mut addr: libc::sockaddr_un = mem::zeroed();
+        addr.sun_family = libc::AF_UNIX as libc::sa_family_t;
+        let bytes = path.as_os_str().as_bytes();
+        if bytes.len() >= addr.sun_path.len() {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, "socket path too long"));
+        }
+        for (d, s) in addr.sun_path.iter_mut().zip(bytes) {
+            *d = *s as libc::c_char;
+        }
+        let addr_len = mem::size_of::<libc::sa_family_t>() + bytes.len() + 1;
+        let space = libc::CMSG_SPACE(mem::size_of::<RawFd>() as u32) as usize;
+        // u64 words keep the control buffer aligned for cmsghdr.
+        let mut control = vec![0u64; space.div_ceil(8)];
+        let mut msg: libc::msghdr = mem::zeroed();
+        msg.msg_name = &mut addr as *mut _ as *mut libc::c_void;
+        msg.msg_namelen = addr_len as libc::socklen_t;
+        msg.msg_control = control.as_mut_ptr() as *mut libc::c_void;
+        msg.msg_controllen = space as _;
+        let cmsg = libc::CMSG_FIRSTHDR(&msg);
+        (*cmsg).cmsg_level = libc::SOL_SOCKET;
+        (*cmsg).cmsg_type = libc::SCM_RIGHTS;
+        (*cmsg).cmsg_len = libc::CMSG_LEN(mem::size_of::<RawFd>() as u32) as _;
+        std::ptr::write_unaligned(libc::CMSG_DATA(cmsg) as *mut RawFd, fd);
+        if libc::sendmsg(sock.as_raw_fd(), &msg, libc::MSG_NOSIGNAL) < 0 {
+            return Err(io::Error::last_os_error());
+        }
+    }
+    Ok(())
+}
+
+/// Writes records to systemd-journald over its native socket.
+pub struct Journald {
+    pub path: String,
+    /// `SYSLOG_IDENTIFIER`; defaults to the logger name.
+    pub identifier: Option<String>,
+    pub errors: Errors,
+    sock: UnixDatagram,
+}
+
+impl Journald {
+    pub fn new(path: String, errors: Errors) -> io::Result<Self> {
+        Ok(Journald { path, identifier: None, errors, sock: UnixDatagram::unbound()? })
+    }
+
+    fn transmit(&self, payload: &[u8]) -> io::Result<()> {
+        match self.sock.send_to(payload, &self.path) {
+            Ok(_) => Ok(()),
+            // Too big for one datagram: hand journald a sealed memfd instead.
+            Err(e) if matches!(e.raw_os_error(), Some(libc::EMSGSIZE | libc::ENOBUFS)) => {
+                let f = memfd(payload)?;
+                send_fd(&self.sock, Path::new(&self.path), f.as_raw_fd())
+            }
+            Err(e) => Err(e),
+        }
+    }
+
+    pub fn send(
+        &self,
+        level: LogLevel,
+        name: &str,
+        message: &str,
+        code: Option<&Code>,
+        fields: &Fields,
+    ) -> io::Result<()> {
+        let payload = encode(level, name, self.identifier.as_deref(), message, code, fields);
+        self.transmit(&payload).or_else(|e| self.errors.handle(e))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::OnError;
+    use serde_json::json;
+    use std::collections::HashMap;
+    use std::io::{Read, Seek};
+
+    fn journald(path: &Path) -> Journald {
+        let path = path.to_string_lossy().to_string();
+        Journald::new(path.clone(), Errors::new(&path, OnError::Raise)).unwrap()
+    }
+
+    /// Parses a native-protocol payload back into fields.
+    fn decode(mut data: &[u8]) -> HashMap<String, Vec<u8>> {
+        let mut out = HashMap::new();
+        while !data.is_empty() {
+            let nl = data.iter().position(|&b| b == b'\n').unwrap();
+            let line = &data[..nl];
+            if let Some(eq) = line.iter().position(|&b| b == b'=') {
+                out.insert(String::from_utf8(line[..eq].to_vec()).unwrap(), line[eq + 1..].to_vec());
+                data = &data[nl + 1..];
+            } else {
+                let k = String::from_utf8(line.to_vec()).unwrap();
+                let len = u64::from_le_bytes(data[nl + 1..nl + 9].try_into().unwrap()) as usize;
+                out.insert(k, data[nl + 9..nl + 9 + len].to_vec());
+                assert_eq!(data[nl + 9 + len], b'\n');
+                data = &data[nl + 10 + len..];
+            }
+        }
+        out
+    }
+
+    /// Receives one datagram and the descriptor passed with it, if any.
+    fn recv(sock: &UnixDatagram) -> (Vec<u8>, Option<File>) {
+        let mut buf = vec![0u8; 256 * 1024];
+        let mut control = vec![0u64; 8];
+        unsafe {
+            let mut iov = libc::iovec { iov_base: buf.as_mut_ptr() as *mut libc::c_void, iov_len: buf.len() };
+            let mut msg: libc::msghdr = mem::zeroed();
+            msg.msg_iov = &mut iov;
+            msg.msg_iovlen = 1;
+            msg.msg_control = control.as_mut_ptr() as *mut libc::c_void;
+            msg.msg_controllen = control.len() * 8;
+            let n = libc::recvmsg(sock.as_raw_fd(), &mut msg, 0);
+            assert!(n >= 0, "{}", io::Error::last_os_error());
+            buf.truncate(n as usize);
+            let cmsg = libc::CMSG_FIRSTHDR(&msg);
+            let fd = (!cmsg.is_null() && (*cmsg).cmsg_type == libc::SCM_RIGHTS)
+                .then(|| File::from_raw_fd(std::ptr::read_unaligned(libc::CMSG_DATA(cmsg) as *const RawFd)));
+            (buf, fd)
+        }
+    }
+
+    fn fields() -> Fields {
+        vec![
+            ("request_id".into(), json!("r-42")),
+            ("elapsed_ms".into(), json!(850.5)),
+            ("priority".into(), json!("ignored")),
+            ("9lives".into(), json!(true)),
+            ("_trusted".into(), json!("no")),
+        ]
+    }
+
+    #[test]
+    fn small_record_is_one_datagram() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("journal.sock");
+        let server = UnixDatagram::bind(&path).unwrap();
+        let code = Code { file: "server/routes/chat.py".into(), line: 42, func: "send_message".into() };
+        journald(&path)
+            .send(LogLevel::WARNING, "routes.chat", "slow\nreply", Some(&code), &fields())
+            .unwrap();
+        let (data, fd) = recv(&server);
+        assert!(fd.is_none());
+        let got = decode(&data);
+        assert_eq!(got["MESSAGE"], b"slow\nreply");
+        assert_eq!(got["PRIORITY"], b"4");
+        assert_eq!(got["LOGGER"], b"routes.chat");
+        assert_eq!(got["SYSLOG_IDENTIFIER"], b"routes.chat");
+        assert_eq!(got["CODE_FUNC"], b"send_message");
+        assert_eq!(got["CODE_LINE"], b"42");
+        assert_eq!(got["REQUEST_ID"], b"r-42");
+        assert_eq!(got["ELAPSED_MS"], b"850.5");
+        assert_eq!(got["F_9LIVES"], b"true");
+        assert_eq!(got["F__TRUSTED"], b"no");
+    }
+
+    #[test]
+    fn large_record_goes_through_memfd() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("journal.sock");
+        let server = UnixDatagram::bind(&path).unwrap();
+        let big = "x".repeat(4 * 1024 * 1024);
+        journald(&path).send(LogLevel::INFO, "llm", &big, None, &Fields::new()).unwrap();
+        let (data, fd) = recv(&server);
+        assert!(data.is_empty());
+        let mut f = fd.expect("payload descriptor");
+        let seals = unsafe { libc::fcntl(f.as_raw_fd(), libc::F_GET_SEALS) };
+        assert_ne!(seals & libc::F_SEAL_WRITE, 0);
+        let mut payload = Vec::new();
+        f.rewind().unwrap();
+        f.read_to_end(&mut payload).unwrap();
+        let got = decode(&payload);
+        assert_eq!(got["MESSAGE"].len(), big.len());
+        assert_eq!(got["PRIORITY"], b"6");
+    }
+
+    #[test]
+    fn missing_socket_follows_on_error() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("absent.sock");
+        assert!(journald(&path).send(LogLevel::INFO, "a", "x", None, &Fields::new()).is_err());
+        let mut quiet = journald(&path);
+        quiet.errors = Errors::new("absent.sock", OnError::Ignore);
+        quiet.send(LogLevel::INFO, "a", "x", None, &Fields::new()).unwrap();
+        assert_eq!(quiet.errors.suppressed(), 1);
+    }
+}
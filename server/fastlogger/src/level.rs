@@ -0,0 +1,194 @@
+use pyo3::exceptions::{PyTypeError, PyValueError};
+use pyo3::prelude::*;
+use pyo3::types::PyBool;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+
+use crate::rules;
+
+/// Severity of a record. Discriminants match the stdlib `logging` numbers.
+#[allow(clippy::upper_case_acronyms)]
+#[derive(Clone, Copy, PartialEq, PartialOrd)]
+pub enum LogLevel {
+    TRACE = 5,
+    DEBUG = 10,
+    INFO = 20,
+    WARNING = 30,
+    ERROR = 40,
+    CRITICAL = 50,
+}
+
+impl LogLevel {
+    pub const ALL: [LogLevel; 6] = [
+        LogLevel::TRACE,
+        LogLevel::DEBUG,
+        LogLevel::INFO,
+        LogLevel::WARNING,
+        LogLevel::ERROR,
+        LogLevel::CRITICAL,
+    ];
+
+    /// Parses a level name, alias or number, e.g. `"warn"`, `"FATAL"`, `"40"`.
+    pub fn parse(level: &str) -> Option<Self> {
+        let level = level.trim();
+        if let Ok(n) = level.parse::<i64>() {
+            return Some(Self::from_number(n));
+        }
+        match level.to_uppercase().as_str() {
+            "TRACE" => Some(LogLevel::TRACE),
+            "DEBUG" => Some(LogLevel::DEBUG),
+            "INFO" => Some(LogLevel::INFO),
+            "WARNING" | "WARN" => Some(LogLevel::WARNING),
+            "ERROR" => Some(LogLevel::ERROR),
+            "CRITICAL" | "FATAL" => Some(LogLevel::CRITICAL),
+            _ => None,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            LogLevel::TRACE => "TRACE",
+            LogLevel::DEBUG => "DEBUG",
+            LogLevel::INFO => "INFO",
+            LogLevel::WARNING => "WARNING",
+            LogLevel::ERROR => "ERROR",
+            LogLevel::CRITICAL => "CRITICAL",
+        }
+    }
+
+    pub fn number(self) -> i64 {
+        self as i64
+    }
+
+    /// Maps a stdlib level number to the highest level not above it, so a
+    /// custom level 25 is treated as INFO and 45 as ERROR.
+    pub fn from_number(level: i64) -> Self {
+        Self::ALL
+            .iter()
+            .rev()
+            .find(|l| l.number() <= level)
+            .copied()
+            .unwrap_or(LogLevel::TRACE)
+    }
+
+    /// Accepts a level name or number from Python.
+    pub fn extract(level: &Bound<'_, PyAny>) -> PyResult<Self> {
+        // bool is an int subclass, but `level=True` is a mistake, not TRACE.
+        if level.is_instance_of::<PyBool>() {
+            return Err(PyTypeError::new_err(format!("log level must be a name or number, not {}", level)));
+        }
+        if let Ok(n) = level.extract::<i64>() {
+            return Ok(Self::from_number(n));
+        }
+        let s: String = level.extract()?;
+        Self::parse(&s).ok_or_else(|| PyValueError::new_err(format!("unknown log level: {}", s)))
+    }
+}
+
+/// Source of generations for level changes; later changes win.
+static GENERATION: AtomicU64 = AtomicU64::new(1);
+
+/// Level set by `set_global_level`, packed with its generation. Zero means unset.
+static GLOBAL: AtomicU64 = AtomicU64::new(0);
+
+/// Packs `level` with a fresh generation.
+pub fn pack(level: LogLevel) -> u64 {
+    (GENERATION.fetch_add(1, Ordering::Relaxed) << 8) | level.number() as u64
+}
+
+/// A logger's threshold, changeable at runtime from any thread.
+///
+/// Every source of a level — the logger's own `set_level`, the per-prefix
+/// rules and `set_global_level` — carries the generation it was set in, and
+/// the most recent one wins. The constructor level has generation zero, so
+/// any of them override it. A global change is a single store that every
+/// logger observes on its next call.
+///
+/// A child made by `bind()` follows its parent until it gets a level of its
+/// own.
+pub struct LevelCell {
+    name: String,
+    own: AtomicU64,
+    /// The matching rule, packed, with the table version it was looked up
+    /// in; the two change together.
+    rule: Mutex<(u64, u64)>,
+    parent: Option<Arc<LevelCell>>,
+}
+
+impl LevelCell {
+    pub fn new(name: &str, level: LogLevel) -> Self {
+        LevelCell {
+            name: name.to_string(),
+            own: AtomicU64::new(level.number() as u64),
+            rule: Mutex::new((u64::MAX, 0)),
+            parent: None,
+        }
+    }
+
+    /// A cell that inherits this one's level until `set` is called on it.
+    pub fn child(self: &Arc<Self>) -> Self {
+        LevelCell {
+            name: self.name.clone(),
+            own: AtomicU64::new(0),
+            rule: Mutex::new((u64::MAX, 0)),
+            parent: Some(self.clone()),
+        }
+    }
+
+    fn rule(&self) -> u64 {
+        // Read before the lookup: a change in between leaves an older
+        // version with a newer rule, which the next call looks up again.
+        let version = rules::version();
+        let mut rule = self.rule.lock().unwrap();
+        if rule.0 != version {
+            *rule = (version, rules::lookup(&self.name));
+        }
+        rule.1
+    }
+
+    /// The winning level, packed.
+    fn current(&self) -> u64 {
+        let own = self.own.load(Ordering::Relaxed);
+        if let (0, Some(parent)) = (own, &self.parent) {
+            return parent.current();
+        }
+        [own, self.rule(), GLOBAL.load(Ordering::Relaxed)]
+            .into_iter()
+            .filter(|p| p & 0xff != 0)
+            .max_by_key(|p| p >> 8)
+            .unwrap_or(LogLevel::INFO as u64)
+    }
+
+    pub fn get(&self) -> LogLevel {
+        LogLevel::from_number((self.current() & 0xff) as i64)
+    }
+
+    pub fn set(&self, level: LogLevel) {
+        self.own.store(pack(level), Ordering::Relaxed);
+    }
+}
+
+pub fn set_global(level: LogLevel) {
+    GLOBAL.store(pack(level), Ordering::Relaxed);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bound_children_inherit_until_set() {
+        let parent = Arc::new(LevelCell::new("bind_test", LogLevel::INFO));
+        let child = parent.child();
+        parent.set(LogLevel::DEBUG);
+        assert!(child.get() == LogLevel::DEBUG);
+        child.set(LogLevel::ERROR);
+        assert!(child.get() == LogLevel::ERROR);
+        assert!(parent.get() == LogLevel::DEBUG);
+        // Once set, the child no longer follows its parent.
+        parent.set(LogLevel::WARNING);
+        assert!(child.get() == LogLevel::ERROR);
+        let grandchild = Arc::new(child).child();
+        assert!(grandchild.get() == LogLevel::ERROR);
+    }
+}
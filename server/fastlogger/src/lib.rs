@@ -1,99 +1,441 @@
+use pyo3::exceptions::PyValueError;
 use pyo3::prelude::*;
-use std::fs::{OpenOptions, rename};
-use std::io::Write;
-use chrono::Local;
-
-#[derive(PartialEq, PartialOrd)]
-enum LogLevel {
-    DEBUG = 0,
-    INFO = 1,
-    WARNING = 2,
-    ERROR = 3,
+use pyo3::types::{PyDict, PyList};
+use chrono::Utc;
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+
+mod clock;
+mod compress;
+mod console;
+mod context;
+mod config;
+mod error;
+mod exception;
+mod fields;
+mod file;
+mod format;
+mod handler;
+mod journald;
+mod level;
+mod lock;
+mod otlp;
+mod registry;
+mod rotation;
+mod rules;
+mod sink;
+mod syslog;
+mod template;
+mod writer;
+
+use config::choice;
+use console::{Color, Console};
+use error::LogError;
+use fields::Fields;
+use exception::ExcInfo;
+use format::{Code, Entry};
+use level::{LevelCell, LogLevel};
+use sink::{Kind, Sink, SinkSpec};
+
+/// Where a logger's records go; swapped as a whole by `configure()`.
+struct Output {
+    sinks: Vec<Arc<Sink>>,
 }
 
-impl LogLevel {
-    fn from_str(level: &str) -> Self {
-        match level.to_uppercase().as_str() {
-            "DEBUG" => LogLevel::DEBUG,
-            "INFO" => LogLevel::INFO,
-            "WARNING" => LogLevel::WARNING,
-            "ERROR" => LogLevel::ERROR,
-            _ => LogLevel::INFO,
+#[pyclass(frozen)]
+struct Logger {
+    name: String,
+    /// Children made by `bind()` inherit it until their own `set_level`.
+    level: Arc<LevelCell>,
+    output: Arc<RwLock<Arc<Output>>>,
+    bound: Fields,
+}
+
+impl Logger {
+    fn with_level(name: &str, level: LevelCell, output: Output) -> Self {
+        Logger {
+            name: name.to_string(),
+            level: Arc::new(level),
+            output: Arc::new(RwLock::new(Arc::new(output))),
+            bound: Fields::new(),
         }
     }
-}
 
-fn rotate(path: &str, max: u64, count: usize) {
-    let m = std::fs::metadata(path).ok();
-    if let Some(meta) = m {
-        if meta.len() >= max {
-            for i in (1..count).rev() {
-                let o = format!("{}.{}", path, i);
-                let n = format!("{}.{}", path, i + 1);
-                if std::path::Path::new(&o).exists() {
-                    rename(&o, &n).ok();
-                }
-            }
-            let f = format!("{}.1", path);
-            rename(path, f).ok();
+    fn from_output(name: &str, output: Output) -> Self {
+        Logger::with_level(name, LevelCell::new(name, LogLevel::INFO), output)
+    }
+
+    fn output(&self) -> Arc<Output> {
+        self.output.read().unwrap().clone()
+    }
+
+    /// True if the threshold passes `level` and some sink takes it.
+    fn wants(&self, level: LogLevel) -> bool {
+        level >= self.level.get() && self.output().sinks.iter().any(|s| s.accepts(level))
+    }
+
+    fn log(&self, py: Python<'_>, level: LogLevel, msg: &str, kwargs: Option<&Bound<'_, PyDict>>) -> PyResult<()> {
+        if !self.wants(level) {
+            return Ok(());
         }
+        let fields = fields::from_kwargs(kwargs)?;
+        self.emit(py, level, level.name(), &self.name, msg, fields, None, None)
     }
-}
 
-#[pyclass]
-struct Logger {
-    name: String,
-    path: String,
-    max_bytes: u64,
-    backup_count: usize,
-    show_output: bool,
-    level: LogLevel,
+    #[allow(clippy::too_many_arguments)]
+    fn emit(
+        &self,
+        py: Python<'_>,
+        level: LogLevel,
+        label: &str,
+        name: &str,
+        msg: &str,
+        fields: Fields,
+        exc: Option<&ExcInfo>,
+        code: Option<Code>,
+    ) -> PyResult<()> {
+        // Call-site fields win over bound ones, which win over the context.
+        let mut all = context::current(py)?;
+        fields::merge(&mut all, self.bound.iter().cloned());
+        fields::merge(&mut all, fields);
+        let out = self.output();
+        let sinks: Vec<_> = out.sinks.iter().filter(|s| s.accepts(level)).collect();
+        let code = match code {
+            None if sinks.iter().any(|s| s.records_code()) => exception::caller(py)?,
+            code => code,
+        };
+        let entry = Entry { level: label, name, msg, fields: &all, exc, code: code.as_ref() };
+        let now = Utc::now();
+        // One failing sink doesn't starve the others; the first error is raised.
+        let mut result = Ok(());
+        for sink in sinks {
+            result = result.and(sink.emit(py, level, now, &entry));
+        }
+        result
+    }
 }
 
 #[pymethods]
 impl Logger {
     #[new]
-    fn new(name: String, path: String, max_bytes: u64, backup_count: usize, show_output: bool, level: String) -> Self {
-        let level_enum = LogLevel::from_str(&level);
-        Logger { name, path, max_bytes, backup_count, show_output, level: level_enum }
+    #[allow(clippy::too_many_arguments)]
+    #[pyo3(signature = (
+        name, path=None, max_bytes=10 * 1024 * 1024, backup_count=5, show_output=false, level=None,
+        format="text", fmt=None, timezone="utc", precision="ms", newlines="indent", max_message_bytes=None,
+        background=false, queue_size=10000, overflow="block",
+        when="size", max_age_days=None, compress=None, on_error="raise", color="auto", stdout=None, stderr=None,
+        sinks=None
+    ))]
+    fn new(
+        name: String,
+        path: Option<String>,
+        max_bytes: u64,
+        backup_count: usize,
+        show_output: bool,
+        level: Option<&Bound<'_, PyAny>>,
+        format: &str,
+        fmt: Option<&str>,
+        timezone: &str,
+        precision: &str,
+        newlines: &str,
+        max_message_bytes: Option<usize>,
+        background: bool,
+        queue_size: usize,
+        overflow: &str,
+        when: &str,
+        max_age_days: Option<f64>,
+        compress: Option<&str>,
+        on_error: &str,
+        color: &str,
+        stdout: Option<Py<PyAny>>,
+        stderr: Option<Py<PyAny>>,
+        sinks: Option<Vec<Bound<'_, PyAny>>>,
+    ) -> PyResult<Self> {
+        let level = LevelCell::new(&name, level.map(LogLevel::extract).transpose()?.unwrap_or(LogLevel::INFO));
+        let formatter = sink::formatter(format, fmt, timezone, precision, newlines, max_message_bytes)?;
+        let mut out = Vec::new();
+        if let Some(path) = path {
+            let spec = sink::file_spec(
+                path, max_bytes, backup_count, when, max_age_days, compress, background, queue_size, overflow, on_error,
+            )?;
+            out.push(SinkSpec { kind: Kind::File(spec), level: None, formatter: formatter.clone() }.open(false)?);
+        }
+        if show_output {
+            let console = Console { stdout, stderr, color: choice("color mode", color, Color::parse)? };
+            out.push(SinkSpec { kind: Kind::Console(console), level: None, formatter }.open(false)?);
+        }
+        for s in sinks.iter().flatten() {
+            out.push(sink::extract(s)?);
+        }
+        if out.is_empty() {
+            return Err(PyValueError::new_err("a logger needs path=, show_output=True or sinks="));
+        }
+        Ok(Logger::with_level(&name, level, Output { sinks: out }))
     }
 
-    fn write(&self, level_str: &str, msg: &str) {
-        let msg_level = LogLevel::from_str(level_str);
-        if msg_level < self.level {
-            return;
-        }
+    #[pyo3(signature = (level, msg, **kwargs))]
+    fn write(&self, level: &Bound<'_, PyAny>, msg: &str, kwargs: Option<&Bound<'_, PyDict>>) -> PyResult<()> {
+        self.log(level.py(), LogLevel::extract(level)?, msg, kwargs)
+    }
 
-        rotate(&self.path, self.max_bytes, self.backup_count);
-        let mut f = OpenOptions::new().create(true).append(true).open(&self.path).unwrap();
-        let ts = Local::now().format("%Y-%m-%d %H:%M:%S").to_string();
-        let line = format!("{} | {} | {} | {}\n", ts, level_str, self.name, msg);
-        f.write_all(line.as_bytes()).ok();
-        if self.show_output {
-            print!("{}", line);
+    /// Writes a stdlib `logging.LogRecord`, keeping its logger name, level
+    /// name, `extra=` attributes and formatted exception.
+    fn emit_record(&self, record: &Bound<'_, PyAny>) -> PyResult<()> {
+        let r = handler::from_record(record)?;
+        let level = LogLevel::from_number(r.levelno);
+        if !self.wants(level) {
+            return Ok(());
         }
+        // Unregistered numeric levels come through as "Level 25".
+        let label = if r.levelname.starts_with("Level ") { level.name() } else { &r.levelname };
+        self.emit(record.py(), level, label, &r.name, &r.msg, r.fields, None, Some(r.code))
+    }
+
+    /// Returns a child logger that adds `kwargs` to every record. It shares
+    /// this logger's output and follows its level until given its own.
+    #[pyo3(signature = (**kwargs))]
+    fn bind(&self, kwargs: Option<&Bound<'_, PyDict>>) -> PyResult<Self> {
+        let mut bound = self.bound.clone();
+        fields::merge(&mut bound, fields::from_kwargs(kwargs)?);
+        Ok(Logger { name: self.name.clone(), level: Arc::new(self.level.child()), output: self.output.clone(), bound })
+    }
+
+    /// Blocks until every queued record has been handed to its file.
+    fn flush(&self, py: Python<'_>) -> PyResult<()> {
+        self.output().sinks.iter().try_for_each(|s| s.flush(py))
+    }
+
+    /// The sinks this logger writes to, as `FileSink`, `SyslogSink`, etc. objects.
+    #[getter]
+    fn sinks<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyList>> {
+        let sinks = self.output().sinks.iter().map(|s| sink::wrap(py, s)).collect::<PyResult<Vec<_>>>()?;
+        PyList::new(py, sinks)
+    }
+
+    #[getter]
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Name of the current threshold, e.g. `"INFO"`.
+    #[getter(level)]
+    fn get_level(&self) -> &'static str {
+        self.level.get().name()
+    }
+
+    #[setter(level)]
+    fn set_level_prop(&self, level: &Bound<'_, PyAny>) -> PyResult<()> {
+        self.set_level(level)
+    }
+
+    /// Changes this logger's threshold; takes a level name or number.
+    fn set_level(&self, level: &Bound<'_, PyAny>) -> PyResult<()> {
+        self.level.set(LogLevel::extract(level)?);
+        Ok(())
     }
 
-    fn info(&self, msg: &str) {
-        self.write("INFO", msg);
+    /// True if a record at `level` would be written.
+    fn is_enabled_for(&self, level: &Bound<'_, PyAny>) -> PyResult<bool> {
+        Ok(self.wants(LogLevel::extract(level)?))
     }
 
-    fn error(&self, msg: &str) {
-        self.write("ERROR", msg);
+    /// Number of write failures swallowed by the `on_error` policy.
+    #[getter]
+    fn suppressed_errors(&self) -> u64 {
+        self.output().sinks.iter().filter_map(|s| s.errors()).map(|e| e.suppressed()).sum()
     }
 
-    fn warning(&self, msg: &str) {
-        self.write("WARNING", msg);
+    /// Number of records discarded because a background queue was full.
+    #[getter]
+    fn dropped(&self) -> u64 {
+        self.output().sinks.iter().map(|s| s.dropped()).sum()
     }
 
-    fn debug(&self, msg: &str) {
-        self.write("DEBUG", msg);
+    #[pyo3(signature = (msg, **kwargs))]
+    fn info(&self, py: Python<'_>, msg: &str, kwargs: Option<&Bound<'_, PyDict>>) -> PyResult<()> {
+        self.log(py, LogLevel::INFO, msg, kwargs)
     }
+
+    #[pyo3(signature = (msg, **kwargs))]
+    fn error(&self, py: Python<'_>, msg: &str, kwargs: Option<&Bound<'_, PyDict>>) -> PyResult<()> {
+        self.log(py, LogLevel::ERROR, msg, kwargs)
+    }
+
+    #[pyo3(signature = (msg, **kwargs))]
+    fn warning(&self, py: Python<'_>, msg: &str, kwargs: Option<&Bound<'_, PyDict>>) -> PyResult<()> {
+        self.log(py, LogLevel::WARNING, msg, kwargs)
+    }
+
+    #[pyo3(signature = (msg, **kwargs))]
+    fn critical(&self, py: Python<'_>, msg: &str, kwargs: Option<&Bound<'_, PyDict>>) -> PyResult<()> {
+        self.log(py, LogLevel::CRITICAL, msg, kwargs)
+    }
+
+    #[pyo3(signature = (msg, **kwargs))]
+    fn debug(&self, py: Python<'_>, msg: &str, kwargs: Option<&Bound<'_, PyDict>>) -> PyResult<()> {
+        self.log(py, LogLevel::DEBUG, msg, kwargs)
+    }
+
+    /// Logs at ERROR with `exc`, or the exception currently being handled,
+    /// and its cause/context chain.
+    #[pyo3(signature = (msg, exc=None, **kwargs))]
+    fn exception(
+        &self,
+        py: Python<'_>,
+        msg: &str,
+        exc: Option<&Bound<'_, PyAny>>,
+        kwargs: Option<&Bound<'_, PyDict>>,
+    ) -> PyResult<()> {
+        if !self.wants(LogLevel::ERROR) {
+            return Ok(());
+        }
+        let exc = match exc {
+            Some(e) => Some(e.clone()),
+            None => exception::active(py)?,
+        };
+        let info = exc.map(|e| exception::capture(&e)).transpose()?;
+        let fields = fields::from_kwargs(kwargs)?;
+        self.emit(py, LogLevel::ERROR, LogLevel::ERROR.name(), &self.name, msg, fields, info.as_ref(), None)
+    }
+
+    #[pyo3(signature = (msg, **kwargs))]
+    fn trace(&self, py: Python<'_>, msg: &str, kwargs: Option<&Bound<'_, PyDict>>) -> PyResult<()> {
+        self.log(py, LogLevel::TRACE, msg, kwargs)
+    }
+}
+
+/// Sets the threshold of every logger at once, until a later `set_level`
+/// or rule change overrides it.
+#[pyfunction]
+fn set_global_level(level: &Bound<'_, PyAny>) -> PyResult<()> {
+    level::set_global(LogLevel::extract(level)?);
+    Ok(())
+}
+
+/// Sets the level for `name` and every dotted name below it; `None` removes
+/// the rule. The most specific rule wins, so `routes.chat` overrides `routes`.
+#[pyfunction]
+#[pyo3(signature = (name, level))]
+fn set_level_for(name: &str, level: Option<&Bound<'_, PyAny>>) -> PyResult<()> {
+    rules::set(name, level.map(LogLevel::extract).transpose()?);
+    Ok(())
+}
+
+/// Replaces all per-name rules with a `{name: level}` dict or a
+/// `"routes=WARNING,chat_service=DEBUG"` spec.
+#[pyfunction]
+fn set_levels(rules: &Bound<'_, PyAny>) -> PyResult<()> {
+    rules::replace(rules::extract(rules)?);
+    Ok(())
+}
+
+/// Returns the logger called `name`, creating it from `config` (the `Logger`
+/// constructor arguments) on first use.
+#[pyfunction]
+#[pyo3(signature = (name, **config))]
+fn get_logger(py: Python<'_>, name: &str, config: Option<&Bound<'_, PyDict>>) -> PyResult<Py<PyAny>> {
+    registry::logger(py, name, || {
+        let kwargs = match config {
+            Some(c) => c.copy()?,
+            None => PyDict::new(py),
+        };
+        if kwargs.is_empty() {
+            let msg = format!("no logger named {:?} yet; pass path= or sinks= to create it", name);
+            return Err(PyValueError::new_err(msg));
+        }
+        kwargs.set_item("name", name)?;
+        Ok(py.get_type::<Logger>().call((), Some(&kwargs))?.unbind())
+    })
+}
+
+fn apply(py: Python<'_>, doc: &serde_json::Value) -> PyResult<()> {
+    let plan = config::plan(doc)?;
+    // Open every sink first, so a bad path leaves the running loggers alone.
+    // Exporters go last, once the file sinks they fall back to are open.
+    let fallbacks: Vec<String> = plan
+        .sinks
+        .values()
+        .filter_map(|s| match &s.kind {
+            Kind::Otlp(o) => o.fallback_name.clone(),
+            _ => None,
+        })
+        .collect();
+    let mut opened = HashMap::new();
+    let mut exporters = Vec::new();
+    for (name, spec) in plan.sinks {
+        if !plan.loggers.iter().any(|(_, sinks)| sinks.contains(&name)) && !fallbacks.contains(&name) {
+            continue;
+        }
+        match spec.kind {
+            Kind::Otlp(_) => exporters.push((name, spec)),
+            _ => {
+                opened.insert(name, spec.open(true)?);
+            }
+        }
+    }
+    for (name, mut spec) in exporters {
+        if let Kind::Otlp(o) = &mut spec.kind {
+            o.fallback = o.fallback_name.as_ref().map(|n| opened[n].clone());
+        }
+        opened.insert(name, spec.open(true)?);
+    }
+    let outputs: Vec<_> = plan
+        .loggers
+        .iter()
+        .map(|(name, sinks)| (name, Output { sinks: sinks.iter().map(|s| opened[s].clone()).collect() }))
+        .collect();
+    let mut levels = plan.levels;
+    // The environment still has the last word on levels.
+    levels.extend(rules::env_table()?);
+    rules::configure(levels);
+    for (name, output) in outputs {
+        let output = Arc::new(output);
+        let logger = registry::logger(py, name, || {
+            let l = Logger::from_output(name, Output { sinks: output.sinks.clone() });
+            Ok(Py::new(py, l)?.into_any())
+        })?;
+        *logger.bind(py).downcast::<Logger>()?.get().output.write().unwrap() = output;
+    }
+    Ok(())
+}
+
+/// Applies a TOML file or an equivalent dict describing sinks, rotation,
+/// formats and levels. Loggers it names are created or updated in place.
+/// Levels set with `set_level_for` or `set_levels` stay above the file's.
+#[pyfunction]
+fn configure(py: Python<'_>, source: &Bound<'_, PyAny>) -> PyResult<()> {
+    let (doc, src) = config::load(source)?;
+    apply(py, &doc)?;
+    config::remember(src);
+    Ok(())
+}
+
+/// Re-applies the last `configure()` source, re-reading it if it was a file.
+#[pyfunction]
+fn reload(py: Python<'_>) -> PyResult<()> {
+    apply(py, &config::reload()?)
 }
 
 #[pymodule]
 fn fastlogger(m: &Bound<'_, PyModule>) -> PyResult<()> {
     m.add_class::<Logger>()?;
+    m.add_function(wrap_pyfunction!(configure, m)?)?;
+    m.add_function(wrap_pyfunction!(reload, m)?)?;
+    m.add_function(wrap_pyfunction!(get_logger, m)?)?;
+    m.add_function(wrap_pyfunction!(set_global_level, m)?)?;
+    m.add_function(wrap_pyfunction!(set_level_for, m)?)?;
+    m.add_function(wrap_pyfunction!(set_levels, m)?)?;
+    rules::load_env()?;
+    let _ = error::STDERR.set(console::to_stderr);
+    m.add("LogError", m.py().get_type::<LogError>())?;
+    for level in LogLevel::ALL {
+        m.add(level.name(), level.number())?;
+    }
+    sink::register(m)?;
+    context::install(m)?;
+    let code = std::ffi::CString::new(handler::HANDLER_PY)?;
+    let handler = PyModule::from_code(m.py(), &code, c"fastlogger/handler.py", c"fastlogger._handler")?;
+    m.add("Handler", handler.getattr("Handler")?)?;
     Ok(())
 }
 
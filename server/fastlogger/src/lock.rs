@@ -0,0 +1,35 @@
+use std::fs::{File, OpenOptions};
+use std::io;
+use std::path::Path;
+
+/// Advisory lock shared by every process writing the same log file.
+///
+/// The lock lives in a hidden sidecar (`logs/.x.log.lock`) so it is never
+/// mistaken for a backup, and is released when the guard is dropped.
+pub struct FileLock {
+    file: File,
+}
+
+fn sidecar(path: &str) -> String {
+    let p = Path::new(path);
+    let name = p.file_name().map(|n| n.to_string_lossy()).unwrap_or_default();
+    match p.parent() {
+        Some(d) if !d.as_os_str().is_empty() => format!("{}/.{}.lock", d.display(), name),
+        _ => format!(".{}.lock", name),
+    }
+}
+
+impl FileLock {
+    /// Blocks until the lock is held. Callers must not rotate without it.
+    pub fn acquire(path: &str) -> io::Result<Self> {
+        let file = OpenOptions::new().create(true).truncate(false).write(true).open(sidecar(path))?;
+        file.lock()?;
+        Ok(FileLock { file })
+    }
+}
+
+impl Drop for FileLock {
+    fn drop(&mut self) {
+        self.file.unlock().ok();
+    }
+}
@@ -0,0 +1,694 @@
+use chrono::{DateTime, Utc};
+use crossbeam_channel::{bounded, Receiver, RecvTimeoutError, Sender, TrySendError};
+use pyo3::prelude::*;
+use pyo3::sync::GILOnceCell;
+use serde_json::{json, Map, Value};
+use std::io::{self, BufRead, BufReader, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+use crate::error::{Errors, OnError};
+use crate::fields::Fields;
+use crate::format::{thread_id, Entry};
+use crate::level::LogLevel;
+use crate::sink::Sink;
+use crate::writer::Writer;
+
+pub const ENDPOINT: &str = "http://localhost:4318/v1/logs";
+pub const SERVICE_NAME: &str = "treechat";
+
+/// Longest wait between attempts to reach a collector that is down.
+const MAX_PROBE: Duration = Duration::from_secs(30);
+
+/// How long dropping an exporter waits for its queue to drain.
+const DRAIN: Duration = Duration::from_secs(2);
+
+/// An `http://host[:port][/path]` collector URL.
+pub struct Endpoint {
+    pub url: String,
+    host: String,
+    port: u16,
+    path: String,
+}
+
+impl Endpoint {
+    pub fn parse(url: &str) -> Result<Self, String> {
+        let rest = match url.strip_prefix("http://") {
+            Some(rest) => rest,
+            None if url.starts_with("https://") => {
+                return Err(format!("{:?}: https is not supported; export to a local collector", url))
+            }
+            None => return Err(format!("{:?} is not an http:// URL", url)),
+        };
+        let (authority, path) = match rest.find('/') {
+            Some(i) => (&rest[..i], &rest[i..]),
+            None => (rest, "/v1/logs"),
+        };
+        let (host, port) = match authority.rsplit_once(':') {
+            Some((host, port)) if !port.contains(']') => {
+                (host, port.parse().map_err(|_| format!("{:?} has an invalid port", url))?)
+            }
+            _ => (authority, 80),
+        };
+        let host = host.trim_start_matches('[').trim_end_matches(']');
+        if host.is_empty() {
+            return Err(format!("{:?} has no host", url));
+        }
+        Ok(Endpoint { url: url.to_string(), host: host.to_string(), port, path: path.to_string() })
+    }
+
+    /// `host:port` for the `Host` header, with IPv6 addresses in brackets.
+    fn authority(&self) -> String {
+        if self.host.contains(':') {
+            format!("[{}]:{}", self.host, self.port)
+        } else {
+            format!("{}:{}", self.host, self.port)
+        }
+    }
+}
+
+/// Checks a configured request header: the name must be an HTTP token and
+/// the value free of control characters, so neither can end the header
+/// line and start another.
+pub fn check_header(name: &str, value: &str) -> Result<(), String> {
+    let token = |c: char| c.is_ascii_alphanumeric() || "!#$%&'*+-.^_`|~".contains(c);
+    if name.is_empty() || !name.chars().all(token) {
+        return Err(format!("invalid header name {:?}", name));
+    }
+    if value.chars().any(|c| c.is_control() && c != '\t') {
+        return Err(format!("header {:?} has a control character in its value", name));
+    }
+    Ok(())
+}
+
+pub struct Settings {
+    pub endpoint: Endpoint,
+    /// Resource attributes, `service.name` first.
+    pub resource: Fields,
+    pub headers: Vec<(String, String)>,
+    pub batch_size: usize,
+    /// How long a partial batch waits for more records.
+    pub flush_interval: Duration,
+    pub max_retries: u32,
+    /// Delay before the first retry, doubled for each one after it.
+    pub retry_backoff: Duration,
+    pub timeout: Duration,
+    pub queue_size: usize,
+}
+
+impl Settings {
+    pub fn new(endpoint: Endpoint, service_name: &str) -> Self {
+        Settings {
+            endpoint,
+            resource: vec![("service.name".into(), json!(service_name))],
+            headers: Vec::new(),
+            batch_size: 512,
+            flush_interval: Duration::from_secs(1),
+            max_retries: 3,
+            retry_backoff: Duration::from_millis(500),
+            timeout: Duration::from_secs(5),
+            queue_size: 10000,
+        }
+    }
+}
+
+fn severity(level: LogLevel) -> u8 {
+    match level {
+        LogLevel::TRACE => 1,
+        LogLevel::DEBUG => 5,
+        LogLevel::INFO => 9,
+        LogLevel::WARNING => 13,
+        LogLevel::ERROR => 17,
+        LogLevel::CRITICAL => 21,
+    }
+}
+
+/// Converts a field value to an OTLP `AnyValue`.
+fn any_value(v: &Value) -> Value {
+    match v {
+        Value::Null => json!({}),
+        Value::Bool(b) => json!({ "boolValue": b }),
+        Value::Number(n) if n.is_f64() => json!({ "doubleValue": n }),
+        // 64-bit integers are strings in OTLP/JSON.
+        Value::Number(n) => json!({ "intValue": n.to_string() }),
+        Value::String(s) => json!({ "stringValue": s }),
+        Value::Array(a) => json!({ "arrayValue": { "values": a.iter().map(any_value).collect::<Vec<_>>() } }),
+        Value::Object(o) => {
+            json!({ "kvlistValue": { "values": o.iter().map(|(k, v)| key_value(k, v)).collect::<Vec<_>>() } })
+        }
+    }
+}
+
+fn key_value(key: &str, v: &Value) -> Value {
+    json!({ "key": key, "value": any_value(v) })
+}
+
+fn hex_id(v: &Value, len: usize) -> Option<String> {
+    let s = v.as_str()?;
+    (s.len() == len && s.bytes().all(|b| b.is_ascii_hexdigit())).then(|| s.to_ascii_lowercase())
+}
+
+/// The active OpenTelemetry span as hex `(trace_id, span_id)`, if the
+/// `opentelemetry` package is installed and a valid span is current.
+pub fn current_span(py: Python<'_>) -> Option<(String, String)> {
+    static GET_SPAN: GILOnceCell<Option<Py<PyAny>>> = GILOnceCell::new();
+    let get = GET_SPAN
+        .get_or_init(py, || {
+            py.import("opentelemetry.trace").and_then(|m| m.getattr("get_current_span")).ok().map(Bound::unbind)
+        })
+        .as_ref()?;
+    let ctx = get.call0(py).ok()?.call_method0(py, "get_span_context").ok()?;
+    let ctx = ctx.bind(py);
+    if !ctx.getattr("is_valid").ok()?.is_truthy().ok()? {
+        return None;
+    }
+    let trace: u128 = ctx.getattr("trace_id").ok()?.extract().ok()?;
+    let span: u64 = ctx.getattr("span_id").ok()?.extract().ok()?;
+    Some((format!("{:032x}", trace), format!("{:016x}", span)))
+}
+
+/// Builds one OTLP `LogRecord`. `trace_id` and `span_id` fields that hold
+/// hex ids take precedence over `span`.
+pub fn record(level: LogLevel, now: DateTime<Utc>, body: &str, entry: &Entry, span: Option<(String, String)>) -> Value {
+    let (mut trace_id, mut span_id) = span.unzip();
+    let mut attributes = Vec::new();
+    for (k, v) in entry.fields {
+        match k.as_str() {
+            "trace_id" if hex_id(v, 32).is_some() => trace_id = hex_id(v, 32),
+            "span_id" if hex_id(v, 16).is_some() => span_id = hex_id(v, 16),
+            _ => attributes.push(key_value(k, v)),
+        }
+    }
+    if let Some(code) = entry.code {
+        attributes.push(key_value("code.filepath", &json!(code.file)));
+        attributes.push(key_value("code.lineno", &json!(code.line)));
+        attributes.push(key_value("code.function", &json!(code.func)));
+    }
+    if let Some(exc) = entry.exc {
+        let mut stack = String::new();
+        exc.render_text(&mut stack);
+        attributes.push(key_value("exception.type", &json!(exc.type_name())));
+        attributes.push(key_value("exception.message", &json!(exc.message())));
+        attributes.push(key_value("exception.stacktrace", &json!(stack)));
+    }
+    attributes.push(key_value("thread.id", &json!(thread_id())));
+    let nanos = now.timestamp_nanos_opt().unwrap_or(0).to_string();
+    let mut r = Map::new();
+    r.insert("timeUnixNano".into(), json!(nanos));
+    r.insert("observedTimeUnixNano".into(), json!(nanos));
+    r.insert("severityNumber".into(), json!(severity(level)));
+    r.insert("severityText".into(), json!(entry.level));
+    r.insert("body".into(), json!({ "stringValue": body }));
+    r.insert("attributes".into(), Value::Array(attributes));
+    if let Some(id) = trace_id {
+        r.insert("traceId".into(), json!(id));
+    }
+    if let Some(id) = span_id {
+        r.insert("spanId".into(), json!(id));
+    }
+    Value::Object(r)
+}
+
+/// Wraps a batch in `resourceLogs`, one scope per logger name.
+fn payload(resource: &Fields, batch: &[Item]) -> Vec<u8> {
+    let mut scopes: Vec<(&str, Vec<&Value>)> = Vec::new();
+    for item in batch {
+        match scopes.iter_mut().find(|(name, _)| *name == item.scope) {
+            Some((_, records)) => records.push(&item.record),
+            None => scopes.push((&item.scope, vec![&item.record])),
+        }
+    }
+    let scope_logs: Vec<Value> = scopes
+        .into_iter()
+        .map(|(name, records)| json!({ "scope": { "name": name }, "logRecords": records }))
+        .collect();
+    let attributes: Vec<Value> = resource.iter().map(|(k, v)| key_value(k, v)).collect();
+    json!({ "resourceLogs": [{ "resource": { "attributes": attributes }, "scopeLogs": scope_logs }] })
+        .to_string()
+        .into_bytes()
+}
+
+/// The request line and headers of a POST carrying `len` bytes.
+fn head(settings: &Settings, len: usize) -> String {
+    let e = &settings.endpoint;
+    let mut head = format!(
+        "POST {} HTTP/1.1\r\nHost: {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n",
+        e.path,
+        e.authority(),
+        len
+    );
+    for (k, v) in &settings.headers {
+        head.push_str(&format!("{}: {}\r\n", k, v));
+    }
+    head.push_str("\r\n");
+    head
+}
+
+/// A record waiting to be exported.
+pub struct Item {
+    pub scope: String,
+    pub record: Value,
+    /// The record as the fallback sink renders it.
+    pub line: Option<String>,
+}
+
+enum Msg {
+    Item(Item),
+    Flush(Sender<()>),
+}
+
+struct Worker {
+    settings: Settings,
+    fallback: Option<Arc<Writer>>,
+    errors: Arc<Errors>,
+    /// While set, batches skip the collector and go to the fallback.
+    down_until: Option<Instant>,
+    probe: Duration,
+    /// Never sent on; disconnects when the exporter is dropped, which cuts
+    /// retries short so the rest of the queue goes to the fallback.
+    stop: Receiver<()>,
+}
+
+impl Worker {
+    fn run(mut self, rx: Receiver<Msg>) {
+        let mut batch = Vec::new();
+        let mut deadline = None;
+        loop {
+            let msg = match deadline {
+                Some(d) => rx.recv_deadline(d),
+                None => rx.recv().map_err(|_| RecvTimeoutError::Disconnected),
+            };
+            match msg {
+                Ok(Msg::Item(item)) => {
+                    deadline.get_or_insert_with(|| Instant::now() + self.settings.flush_interval);
+                    batch.push(item);
+                    if batch.len() < self.settings.batch_size {
+                        continue;
+                    }
+                }
+                Ok(Msg::Flush(ack)) => {
+                    self.ship(std::mem::take(&mut batch));
+                    deadline = None;
+                    ack.send(()).ok();
+                    continue;
+                }
+                Err(RecvTimeoutError::Timeout) => {}
+                Err(RecvTimeoutError::Disconnected) => {
+                    self.ship(batch);
+                    return;
+                }
+            }
+            self.ship(std::mem::take(&mut batch));
+            deadline = None;
+        }
+    }
+
+    fn ship(&mut self, batch: Vec<Item>) {
+        if batch.is_empty() {
+            return;
+        }
+        let result = match self.down_until {
+            Some(t) if Instant::now() < t => Err(io::Error::other("collector is down")),
+            _ => self.deliver(&payload(&self.settings.resource, &batch)),
+        };
+        match result {
+            Ok(()) => {
+                self.down_until = None;
+                self.probe = self.settings.retry_backoff;
+            }
+            Err(e) => {
+                if self.down_until.is_none_or(|t| Instant::now() >= t) {
+                    self.down_until = Some(Instant::now() + self.probe);
+                    self.probe = (self.probe * 2).min(MAX_PROBE);
+                }
+                self.fall_back(batch, e);
+            }
+        }
+    }
+
+    /// Posts `body`, retrying with exponential backoff.
+    fn deliver(&self, body: &[u8]) -> io::Result<()> {
+        let mut delay = self.settings.retry_backoff;
+        let mut attempt = 0;
+        loop {
+            match self.post(body) {
+                Ok(()) => return Ok(()),
+                Err(e) if attempt >= self.settings.max_retries => return Err(e),
+                Err(e) => {
+                    if let Err(RecvTimeoutError::Disconnected) = self.stop.recv_timeout(delay) {
+                        return Err(e);
+                    }
+                    delay *= 2;
+                    attempt += 1;
+                }
+            }
+        }
+    }
+
+    fn post(&self, body: &[u8]) -> io::Result<()> {
+        let e = &self.settings.endpoint;
+        let timeout = self.settings.timeout;
+        let addr = (e.host.as_str(), e.port)
+            .to_socket_addrs()?
+            .next()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, format!("cannot resolve {}", e.host)))?;
+        let mut s = TcpStream::connect_timeout(&addr, timeout)?;
+        s.set_read_timeout(Some(timeout))?;
+        s.set_write_timeout(Some(timeout))?;
+        s.write_all(head(&self.settings, body.len()).as_bytes())?;
+        s.write_all(body)?;
+        let mut status = String::new();
+        BufReader::new(s).read_line(&mut status)?;
+        match status.split_whitespace().nth(1).and_then(|c| c.parse::<u16>().ok()) {
+            Some(code) if (200..300).contains(&code) => Ok(()),
+            Some(code) => Err(io::Error::other(format!("collector answered HTTP {}", code))),
+            None => Err(io::Error::new(io::ErrorKind::InvalidData, format!("bad response {:?}", status.trim()))),
+        }
+    }
+
+    fn fall_back(&self, batch: Vec<Item>, e: io::Error) {
+        let writer = match &self.fallback {
+            Some(w) => w,
+            None => return self.errors.defer(e),
+        };
+        for line in batch.into_iter().filter_map(|item| item.line) {
+            if let Err(e) = writer.write(line) {
+                self.errors.defer(e);
+                return;
+            }
+        }
+    }
+}
+
+/// Exports records to an OTLP/HTTP collector from a background thread,
+/// writing them to `fallback` while the collector is down.
+pub struct Exporter {
+    pub errors: Arc<Errors>,
+    tx: Option<Sender<Msg>>,
+    stop: Option<Sender<()>>,
+    /// Disconnects when the thread is done.
+    done: Receiver<()>,
+    dropped: AtomicU64,
+    handle: Option<JoinHandle<()>>,
+}
+
+impl Exporter {
+    pub fn spawn(settings: Settings, fallback: Option<Arc<Writer>>, errors: Errors) -> Self {
+        let errors = Arc::new(errors);
+        let (tx, rx) = bounded(settings.queue_size.max(1));
+        let (stop, stop_rx) = bounded(0);
+        let (done_tx, done) = bounded::<()>(0);
+        let worker = Worker {
+            probe: settings.retry_backoff,
+            settings,
+            fallback,
+            errors: errors.clone(),
+            down_until: None,
+            stop: stop_rx,
+        };
+        let handle = std::thread::Builder::new()
+            .name("fastlogger-otlp".into())
+            .spawn(move || {
+                let _done = done_tx;
+                worker.run(rx)
+            })
+            .expect("failed to spawn fastlogger otlp thread");
+        Exporter { errors, tx: Some(tx), stop: Some(stop), done, dropped: AtomicU64::new(0), handle: Some(handle) }
+    }
+
+    /// Queues `item`, dropping it if the queue is full, and returns any
+    /// export failure the thread hit since the last call.
+    pub fn send(&self, item: Item) -> io::Result<()> {
+        if let Some(tx) = &self.tx {
+            if let Err(TrySendError::Full(_)) = tx.try_send(Msg::Item(item)) {
+                self.dropped.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+        self.errors.take_pending()
+    }
+
+    /// Exports everything queued so far.
+    pub fn flush(&self) -> io::Result<()> {
+        if let Some(tx) = &self.tx {
+            let (ack, done) = bounded(1);
+            if tx.send(Msg::Flush(ack)).is_ok() {
+                done.recv().ok();
+            }
+        }
+        self.errors.take_pending()
+    }
+
+    pub fn dropped(&self) -> u64 {
+        self.dropped.load(Ordering::Relaxed)
+    }
+}
+
+/// An exporter before it is started.
+pub struct OtlpSpec {
+    pub settings: Settings,
+    pub on_error: OnError,
+    pub fallback: Option<Arc<Sink>>,
+    /// The configured sink to use as `fallback`, resolved when applied.
+    pub fallback_name: Option<String>,
+}
+
+impl OtlpSpec {
+    pub fn start(self) -> Otlp {
+        let errors = Errors::new(&self.settings.endpoint.url, self.on_error);
+        let writer = self.fallback.as_ref().and_then(|s| s.writer()).cloned();
+        Otlp { exporter: Exporter::spawn(self.settings, writer, errors), fallback: self.fallback }
+    }
+}
+
+/// An exporter and the file sink it falls back to.
+pub struct Otlp {
+    pub exporter: Exporter,
+    pub fallback: Option<Arc<Sink>>,
+}
+
+impl Drop for Exporter {
+    /// Exports what is queued, giving up on retries, and waits at most
+    /// `DRAIN` for it: this usually runs with the GIL held. A thread still
+    /// busy after that is left to finish on its own.
+    fn drop(&mut self) {
+        drop(self.tx.take());
+        drop(self.stop.take());
+        if let Err(RecvTimeoutError::Disconnected) = self.done.recv_timeout(DRAIN) {
+            if let Some(h) = self.handle.take() {
+                h.join().ok();
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::FileSpec;
+    use crate::file::FileTarget;
+    use std::io::Read;
+    use std::net::TcpListener;
+    use std::sync::Mutex;
+
+    /// A collector stand-in answering with `statuses` in turn, then 200.
+    fn collector(statuses: Vec<u16>) -> (String, Receiver<Value>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/v1/logs", listener.local_addr().unwrap());
+        let (tx, rx) = crossbeam_channel::unbounded();
+        let statuses = Mutex::new(statuses.into_iter());
+        std::thread::spawn(move || {
+            for conn in listener.incoming() {
+                let mut r = BufReader::new(conn.unwrap());
+                let mut len = 0;
+                loop {
+                    let mut line = String::new();
+                    r.read_line(&mut line).unwrap();
+                    if line == "\r\n" {
+                        break;
+                    }
+                    if let Some(v) = line.to_ascii_lowercase().strip_prefix("content-length:") {
+                        len = v.trim().parse().unwrap();
+                    }
+                }
+                let mut body = vec![0; len];
+                r.read_exact(&mut body).unwrap();
+                let status = statuses.lock().unwrap().next().unwrap_or(200);
+                if status == 200 {
+                    tx.send(serde_json::from_slice(&body).unwrap()).unwrap();
+                }
+                write!(r.get_mut(), "HTTP/1.1 {} X\r\nContent-Length: 0\r\n\r\n", status).unwrap();
+            }
+        });
+        (url, rx)
+    }
+
+    fn settings(url: &str) -> Settings {
+        let mut s = Settings::new(Endpoint::parse(url).unwrap(), "treechat");
+        s.retry_backoff = Duration::from_millis(10);
+        s.flush_interval = Duration::from_millis(50);
+        s
+    }
+
+    fn item(name: &str, msg: &str, fields: &Fields) -> Item {
+        let entry = Entry { level: "INFO", name, msg, fields, exc: None, code: None };
+        let record = record(LogLevel::INFO, Utc::now(), msg, &entry, None);
+        Item { scope: name.into(), record, line: Some(format!("{}\n", msg)) }
+    }
+
+    fn records(payload: &Value) -> Vec<&Value> {
+        payload["resourceLogs"][0]["scopeLogs"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .flat_map(|s| s["logRecords"].as_array().unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn batches_records_with_resource_and_trace_ids() {
+        let (url, rx) = collector(vec![]);
+        let mut s = settings(&url);
+        s.batch_size = 3;
+        let otlp = Exporter::spawn(s, None, Errors::new(&url, OnError::Raise));
+        let traced = vec![
+            ("trace_id".to_string(), json!("4BF92F3577B34DA6A3CE929D0E0E4736")),
+            ("span_id".to_string(), json!("00f067aa0ba902b7")),
+            ("user".to_string(), json!("ana")),
+            ("ms".to_string(), json!(850)),
+        ];
+        otlp.send(item("chat_service", "reply sent", &traced)).unwrap();
+        otlp.send(item("routes", "GET /chat", &Vec::new())).unwrap();
+        otlp.send(item("chat_service", "done", &Vec::new())).unwrap();
+        let got = rx.recv_timeout(Duration::from_secs(5)).unwrap();
+        let resource = &got["resourceLogs"][0]["resource"]["attributes"][0];
+        assert_eq!(resource, &json!({ "key": "service.name", "value": { "stringValue": "treechat" } }));
+        let scopes = got["resourceLogs"][0]["scopeLogs"].as_array().unwrap();
+        assert_eq!(scopes.len(), 2);
+        assert_eq!(scopes[0]["scope"]["name"], "chat_service");
+        assert_eq!(scopes[0]["logRecords"].as_array().unwrap().len(), 2);
+        let r = &scopes[0]["logRecords"][0];
+        assert_eq!(r["traceId"], "4bf92f3577b34da6a3ce929d0e0e4736");
+        assert_eq!(r["spanId"], "00f067aa0ba902b7");
+        assert_eq!(r["severityNumber"], 9);
+        assert_eq!(r["body"]["stringValue"], "reply sent");
+        let attrs = r["attributes"].as_array().unwrap();
+        assert_eq!(attrs[0], json!({ "key": "user", "value": { "stringValue": "ana" } }));
+        assert_eq!(attrs[1], json!({ "key": "ms", "value": { "intValue": "850" } }));
+        assert!(scopes[1]["logRecords"][0].get("traceId").is_none());
+    }
+
+    #[test]
+    fn retries_after_unavailable() {
+        let (url, rx) = collector(vec![503, 503]);
+        let otlp = Exporter::spawn(settings(&url), None, Errors::new(&url, OnError::Raise));
+        otlp.send(item("a", "kept", &Vec::new())).unwrap();
+        otlp.flush().unwrap();
+        let got = rx.recv_timeout(Duration::from_secs(5)).unwrap();
+        assert_eq!(records(&got)[0]["body"]["stringValue"], "kept");
+    }
+
+    #[test]
+    fn falls_back_to_file_while_collector_is_down() {
+        let dir = tempfile::tempdir().unwrap();
+        let (path, fallback) = fallback(dir.path());
+        // Nothing listens on a port we bound and released.
+        let url = format!("http://{}/v1/logs", TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap());
+        let mut s = settings(&url);
+        s.max_retries = 1;
+        let otlp = Exporter::spawn(s, Some(fallback), Errors::new(&url, OnError::Raise));
+        otlp.send(item("a", "first", &Vec::new())).unwrap();
+        otlp.flush().unwrap();
+        otlp.send(item("a", "second", &Vec::new())).unwrap();
+        otlp.flush().unwrap();
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "first\nsecond\n");
+    }
+
+    #[test]
+    fn recovers_once_collector_is_back() {
+        // Two failed attempts mark the collector down for one backoff period.
+        let (url, rx) = collector(vec![503, 503]);
+        let mut s = settings(&url);
+        s.max_retries = 1;
+        let otlp = Exporter::spawn(s, None, Errors::new(&url, OnError::Ignore));
+        otlp.send(item("a", "lost", &Vec::new())).unwrap();
+        otlp.flush().unwrap();
+        assert_eq!(otlp.errors.suppressed(), 1);
+        std::thread::sleep(Duration::from_millis(30));
+        otlp.send(item("a", "back", &Vec::new())).unwrap();
+        otlp.flush().unwrap();
+        let got = rx.recv_timeout(Duration::from_secs(5)).unwrap();
+        assert_eq!(records(&got)[0]["body"]["stringValue"], "back");
+    }
+
+    fn fallback(dir: &std::path::Path) -> (std::path::PathBuf, Arc<Writer>) {
+        let path = dir.join("fallback.log");
+        let path_str: String = path.to_string_lossy().into();
+        let target = FileTarget::new(path_str.clone(), FileSpec::new(path_str).rotation).unwrap();
+        (path, Arc::new(Writer::direct(target, Errors::new("fallback.log", OnError::Raise))))
+    }
+
+    #[test]
+    fn drop_cuts_retries_short() {
+        let dir = tempfile::tempdir().unwrap();
+        let (path, writer) = fallback(dir.path());
+        let url = format!("http://{}/v1/logs", TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap());
+        let mut s = settings(&url);
+        s.retry_backoff = Duration::from_secs(30);
+        let otlp = Exporter::spawn(s, Some(writer), Errors::new(&url, OnError::Ignore));
+        otlp.send(item("a", "queued", &Vec::new())).unwrap();
+        // Let the batch go out and fail, so the thread sleeps before a retry.
+        std::thread::sleep(Duration::from_millis(200));
+        let start = Instant::now();
+        drop(otlp);
+        assert!(start.elapsed() < Duration::from_secs(1), "{:?}", start.elapsed());
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "queued\n");
+    }
+
+    #[test]
+    fn drop_gives_up_on_a_stuck_collector() {
+        // Accepts connections but never answers.
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/v1/logs", listener.local_addr().unwrap());
+        let mut s = settings(&url);
+        s.timeout = Duration::from_secs(30);
+        let otlp = Exporter::spawn(s, None, Errors::new(&url, OnError::Ignore));
+        otlp.send(item("a", "stuck", &Vec::new())).unwrap();
+        std::thread::sleep(Duration::from_millis(200));
+        let start = Instant::now();
+        drop(otlp);
+        assert!(start.elapsed() < DRAIN + Duration::from_secs(1), "{:?}", start.elapsed());
+    }
+
+    #[test]
+    fn parses_endpoints() {
+        let e = Endpoint::parse("http://collector:4318").unwrap();
+        assert_eq!((e.host.as_str(), e.port, e.path.as_str()), ("collector", 4318, "/v1/logs"));
+        let e = Endpoint::parse("http://[::1]/otlp/v1/logs").unwrap();
+        assert_eq!((e.host.as_str(), e.port, e.path.as_str()), ("::1", 80, "/otlp/v1/logs"));
+        assert!(Endpoint::parse("https://collector").is_err());
+        assert!(Endpoint::parse("collector:4318").is_err());
+    }
+
+    #[test]
+    fn request_head() {
+        let mut s = settings("http://[::1]:4318/v1/logs");
+        s.headers.push(("x-api-key".into(), "k\t1".into()));
+        let head = head(&s, 42);
+        assert!(head.starts_with("POST /v1/logs HTTP/1.1\r\nHost: [::1]:4318\r\n"), "{}", head);
+        assert!(head.ends_with("Content-Length: 42\r\nConnection: close\r\nx-api-key: k\t1\r\n\r\n"), "{}", head);
+        assert!(settings("http://collector/v1/logs").endpoint.authority() == "collector:80");
+    }
+
+    #[test]
+    fn rejects_header_injection() {
+        assert!(check_header("Authorization", "Bearer abc").is_ok());
+        assert!(check_header("x-key", "a\r\nX-Evil: 1").is_err());
+        assert!(check_header("x-key", "a\nb").is_err());
+        assert!(check_header("x-key\r\nX-Evil", "1").is_err());
+        assert!(check_header("x key", "1").is_err());
+        assert!(check_header("", "1").is_err());
+    }
+}
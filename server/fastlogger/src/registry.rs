@@ -0,0 +1,111 @@
+use pyo3::prelude::*;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, Weak};
+
+use crate::config::FileSpec;
+use crate::writer::Writer;
+
+type Writers = HashMap<PathBuf, (Weak<Writer>, FileSpec)>;
+
+/// Open writers by canonical path, so every logger for a file shares one
+/// handle, one queue and one rotation state.
+static WRITERS: Mutex<Option<Writers>> = Mutex::new(None);
+
+/// Loggers handed out by `get_logger`, by name.
+static LOGGERS: Mutex<Option<HashMap<String, Py<PyAny>>>> = Mutex::new(None);
+
+/// Resolves `path` through symlinks and `..` even before the file exists.
+fn canonical(path: &str) -> PathBuf {
+    let p = Path::new(path);
+    if let Ok(c) = p.canonicalize() {
+        return c;
+    }
+    let dir = match p.parent() {
+        Some(d) if !d.as_os_str().is_empty() => d,
+        _ => Path::new("."),
+    };
+    match (dir.canonicalize(), p.file_name()) {
+        (Ok(d), Some(n)) => d.join(n),
+        _ => p.to_path_buf(),
+    }
+}
+
+/// Returns the live writer for `spec.path`, or builds one with `open`.
+///
+/// Normally the first logger to open a path decides its rotation, queue and
+/// error settings and later loggers share that writer as is. With `replace`,
+/// a writer opened with different settings is switched to `spec` in place
+/// by `update`, so loggers outside the new configuration follow too.
+pub fn writer<E>(
+    spec: &FileSpec,
+    replace: bool,
+    open: impl FnOnce() -> Result<Writer, E>,
+    update: impl FnOnce(&Writer) -> Result<(), E>,
+) -> Result<Arc<Writer>, E> {
+    let key = canonical(&spec.path);
+    let mut guard = WRITERS.lock().unwrap();
+    let writers = guard.get_or_insert_with(HashMap::new);
+    if let Some((w, existing)) = writers.get_mut(&key) {
+        if let Some(w) = w.upgrade() {
+            if replace && existing != spec {
+                update(&w)?;
+                *existing = spec.clone();
+            }
+            return Ok(w);
+        }
+    }
+    writers.retain(|_, (w, _)| w.strong_count() > 0);
+    let w = Arc::new(open()?);
+    writers.insert(key, (Arc::downgrade(&w), spec.clone()));
+    Ok(w)
+}
+
+/// Returns the cached logger called `name`, or registers the one `create` builds.
+pub fn logger(py: Python<'_>, name: &str, create: impl FnOnce() -> PyResult<Py<PyAny>>) -> PyResult<Py<PyAny>> {
+    if let Some(l) = LOGGERS.lock().unwrap().as_ref().and_then(|m| m.get(name)) {
+        return Ok(l.clone_ref(py));
+    }
+    // Built without the lock held: the constructor runs Python code.
+    let created = create()?;
+    let mut guard = LOGGERS.lock().unwrap();
+    let loggers = guard.get_or_insert_with(HashMap::new);
+    Ok(loggers.entry(name.to_string()).or_insert(created).clone_ref(py))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::{Errors, OnError};
+    use crate::file::FileTarget;
+    use std::io;
+
+    fn open(spec: &FileSpec) -> io::Result<Arc<Writer>> {
+        let target = || FileTarget::new(spec.path.clone(), spec.rotation);
+        writer(
+            spec,
+            true,
+            || Ok(Writer::direct(target()?, Errors::new(&spec.path, spec.on_error))),
+            |w| w.reconfigure(spec.on_error, spec.background.then_some((spec.queue_size, spec.overflow)), target),
+        )
+    }
+
+    #[test]
+    fn replacing_settings_keeps_one_writer() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut spec = FileSpec::new(dir.path().join("shared.log").to_string_lossy().into());
+        let old = open(&spec).unwrap();
+        old.write("before\n".into()).unwrap();
+        spec.rotation.max_bytes = 16;
+        spec.background = true;
+        spec.on_error = OnError::Ignore;
+        let new = open(&spec).unwrap();
+        assert!(Arc::ptr_eq(&old, &new));
+        // A logger still holding the old handle rotates by the new settings.
+        old.write("after the swap\n".into()).unwrap();
+        old.flush().unwrap();
+        let backup = std::fs::read_to_string(dir.path().join("shared.log.1")).unwrap();
+        assert_eq!(backup, "before\n");
+        assert_eq!(std::fs::read_to_string(dir.path().join("shared.log")).unwrap(), "after the swap\n");
+    }
+}
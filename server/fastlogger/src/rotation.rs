@@ -0,0 +1,243 @@
+use chrono::{Datelike, Duration as Days, Local, NaiveDateTime, Timelike};
+use std::fs::rename;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+use crate::compress::{self, Compression, EXTS};
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Interval {
+    Hourly,
+    Daily,
+    Weekly,
+}
+
+impl Interval {
+    /// Start of the period containing `t`.
+    pub fn start(self, t: NaiveDateTime) -> NaiveDateTime {
+        let hour = t.date().and_hms_opt(t.hour(), 0, 0).unwrap();
+        let day = t.date().and_hms_opt(0, 0, 0).unwrap();
+        match self {
+            Interval::Hourly => hour,
+            Interval::Daily => day,
+            Interval::Weekly => day - Days::days(t.weekday().num_days_from_monday() as i64),
+        }
+    }
+
+    /// Suffix for a backup holding the period starting at `start`.
+    pub fn suffix(self, start: NaiveDateTime) -> String {
+        match self {
+            Interval::Hourly => start.format("%Y-%m-%d_%H").to_string(),
+            Interval::Daily | Interval::Weekly => start.format("%Y-%m-%d").to_string(),
+        }
+    }
+}
+
+/// When a log file is rolled over: on size, on a calendar boundary, or both.
+#[derive(Clone, Copy, PartialEq)]
+pub struct Policy {
+    pub size: bool,
+    pub interval: Option<Interval>,
+}
+
+impl Policy {
+    /// Parses `size`, `hourly`, `daily`, `weekly` or a `+`-joined combination
+    /// such as `daily+size`.
+    pub fn parse(when: &str) -> Option<Self> {
+        let mut policy = Policy { size: false, interval: None };
+        for part in when.to_lowercase().split('+') {
+            let interval = match part.trim() {
+                "size" => {
+                    policy.size = true;
+                    continue;
+                }
+                "hourly" => Interval::Hourly,
+                "daily" | "midnight" => Interval::Daily,
+                "weekly" => Interval::Weekly,
+                _ => return None,
+            };
+            if policy.interval.replace(interval).is_some() {
+                return None;
+            }
+        }
+        Some(policy)
+    }
+}
+
+/// Everything that decides when and how a file is rolled over.
+#[derive(Clone, Copy, PartialEq)]
+pub struct Settings {
+    pub max_bytes: u64,
+    pub backup_count: usize,
+    pub policy: Policy,
+    pub max_age: Option<Duration>,
+    pub compression: Option<Compression>,
+}
+
+pub fn now() -> NaiveDateTime {
+    Local::now().naive_local()
+}
+
+/// Period start for a file last modified at `modified`.
+pub fn period_of(interval: Interval, modified: SystemTime) -> NaiveDateTime {
+    let t: chrono::DateTime<Local> = modified.into();
+    interval.start(t.naive_local())
+}
+
+/// Shifts `x.log.1 .. x.log.N-1` up by one and moves `x.log` to `x.log.1`.
+/// Compressed backups keep their extension as they move down the chain.
+pub fn rotate_numbered(path: &str, count: usize) -> io::Result<()> {
+    for ext in EXTS {
+        std::fs::remove_file(format!("{}.{}{}", path, count, ext)).ok();
+    }
+    for i in (1..count).rev() {
+        for ext in EXTS {
+            let o = format!("{}.{}{}", path, i, ext);
+            let n = format!("{}.{}{}", path, i + 1, ext);
+            if Path::new(&o).exists() {
+                rename(&o, &n)?;
+            }
+        }
+    }
+    let f = format!("{}.1", path);
+    rename_live(path, &f)
+}
+
+/// Renames the live file, tolerating it having been removed by someone else.
+fn rename_live(path: &str, target: &str) -> io::Result<()> {
+    match rename(path, target) {
+        Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e),
+        _ => Ok(()),
+    }
+}
+
+/// Moves `x.log` to `x.log.<suffix>`, adding a counter if that name is taken.
+pub fn rotate_dated(path: &str, suffix: &str) -> io::Result<()> {
+    let taken = |t: &str| EXTS.iter().any(|e| Path::new(&format!("{}{}", t, e)).exists());
+    let mut target = format!("{}.{}", path, suffix);
+    let mut i = 1;
+    while taken(&target) {
+        target = format!("{}.{}.{}", path, suffix, i);
+        i += 1;
+    }
+    rename_live(path, &target)
+}
+
+/// True for suffixes this crate writes: `N`, `YYYY-MM-DD[_HH][.N]`, each
+/// optionally compressed, and `.part` files left by an interrupted compression.
+fn is_backup_suffix(suffix: &str) -> bool {
+    let digits = |s: &str, n: usize| s.len() == n && s.bytes().all(|b| b.is_ascii_digit());
+    let (base, partial) = match suffix.strip_suffix(compress::PARTIAL) {
+        Some(b) => (b, true),
+        None => (suffix, false),
+    };
+    let plain = compress::strip_ext(base);
+    if partial && plain == base {
+        return false;
+    }
+    if !plain.is_empty() && plain.bytes().all(|b| b.is_ascii_digit()) {
+        return true;
+    }
+    let (date, counter) = match plain.split_once('.') {
+        Some((d, c)) => (d, Some(c)),
+        None => (plain, None),
+    };
+    let (day, hour) = match date.split_once('_') {
+        Some((d, h)) => (d, Some(h)),
+        None => (date, None),
+    };
+    let mut parts = day.split('-');
+    let dated = [4, 2, 2].iter().all(|&n| parts.next().is_some_and(|p| digits(p, n))) && parts.next().is_none();
+    dated
+        && hour.is_none_or(|h| digits(h, 2))
+        && counter.is_none_or(|c| !c.is_empty() && c.bytes().all(|b| b.is_ascii_digit()))
+}
+
+/// Lists existing backups of `path` as (file, suffix) pairs.
+pub fn backups(path: &str) -> Vec<(PathBuf, String)> {
+    let p = Path::new(path);
+    let prefix = match p.file_name().and_then(|n| n.to_str()) {
+        Some(n) => format!("{}.", n),
+        None => return Vec::new(),
+    };
+    let dir = match p.parent() {
+        Some(d) if !d.as_os_str().is_empty() => d,
+        _ => Path::new("."),
+    };
+    let entries = match std::fs::read_dir(dir) {
+        Ok(e) => e,
+        Err(_) => return Vec::new(),
+    };
+    entries
+        .flatten()
+        .filter_map(|e| {
+            let name = e.file_name().into_string().ok()?;
+            let suffix = name.strip_prefix(&prefix)?;
+            is_backup_suffix(suffix).then(|| (e.path(), suffix.to_string()))
+        })
+        .collect()
+}
+
+/// Keeps only the newest `count` dated backups of `path`.
+pub fn prune_dated(path: &str, count: usize) {
+    let mut dated: Vec<_> = backups(path)
+        .into_iter()
+        .filter(|(_, s)| !s.ends_with(compress::PARTIAL))
+        .filter(|(_, s)| !compress::strip_ext(s).chars().all(|c| c.is_ascii_digit()))
+        .collect();
+    dated.sort_by(|a, b| b.1.cmp(&a.1));
+    for (p, _) in dated.into_iter().skip(count) {
+        std::fs::remove_file(p).ok();
+    }
+}
+
+/// Deletes backups of `path` last modified more than `max_age` ago.
+pub fn prune_aged(path: &str, max_age: Duration) {
+    let cutoff = match SystemTime::now().checked_sub(max_age) {
+        Some(c) => c,
+        None => return,
+    };
+    for (p, _) in backups(path) {
+        let old = std::fs::metadata(&p)
+            .and_then(|m| m.modified())
+            .map(|t| t < cutoff)
+            .unwrap_or(false);
+        if old {
+            std::fs::remove_file(p).ok();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_our_suffixes_are_backups() {
+        for s in ["1", "12.gz", "3.zst", "2026-10-16", "2026-10-16_09", "2026-10-16.2.gz", "1.gz.part"] {
+            assert!(is_backup_suffix(s), "{}", s);
+        }
+        for s in ["bak", "old", "swp", "1.part", "2026-10", "2026-10-16_9", "2026-10-16.", "1.bak", ""] {
+            assert!(!is_backup_suffix(s), "{}", s);
+        }
+    }
+
+    #[test]
+    fn pruning_leaves_foreign_files_alone() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("chat.log");
+        let path = path.to_str().unwrap();
+        for name in ["chat.log.bak", "chat.log.old", "chat.log.swp", "chat.log.2026-10-14", "chat.log.2026-10-15"] {
+            std::fs::write(dir.path().join(name), "x").unwrap();
+        }
+        prune_dated(path, 1);
+        prune_aged(path, Duration::ZERO);
+        let mut left: Vec<_> = std::fs::read_dir(dir.path())
+            .unwrap()
+            .map(|e| e.unwrap().file_name().into_string().unwrap())
+            .collect();
+        left.sort();
+        assert_eq!(left, ["chat.log.bak", "chat.log.old", "chat.log.swp"]);
+    }
+}
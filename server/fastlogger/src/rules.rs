@@ -0,0 +1,177 @@
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+use pyo3::types::PyDict;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::RwLock;
+
+use crate::level::{self, LogLevel};
+
+/// Environment variable read at import, e.g. `routes=WARNING,chat_service=DEBUG`.
+pub const ENV_VAR: &str = "FASTLOGGER_LEVELS";
+
+struct Rule {
+    prefix: String,
+    /// Level packed with the generation it was set in.
+    packed: u64,
+    /// Set from code rather than by `configure()`, which keeps it.
+    runtime: bool,
+}
+
+static RULES: RwLock<Vec<Rule>> = RwLock::new(Vec::new());
+
+/// Bumped on every change so loggers know their cached match is stale.
+static VERSION: AtomicU64 = AtomicU64::new(0);
+
+/// True if `name` is `prefix` or one of its dotted descendants.
+fn covers(prefix: &str, name: &str) -> bool {
+    match name.strip_prefix(prefix) {
+        Some(rest) => rest.is_empty() || rest.starts_with('.'),
+        None => false,
+    }
+}
+
+pub fn version() -> u64 {
+    VERSION.load(Ordering::Acquire)
+}
+
+/// The most specific rule covering `name`, packed, or 0 if none does.
+pub fn lookup(name: &str) -> u64 {
+    RULES
+        .read()
+        .unwrap()
+        .iter()
+        .filter(|r| covers(&r.prefix, name))
+        .max_by_key(|r| r.prefix.len())
+        .map_or(0, |r| r.packed)
+}
+
+pub fn set(prefix: &str, level: Option<LogLevel>) {
+    let mut rules = RULES.write().unwrap();
+    rules.retain(|r| r.prefix != prefix);
+    if let Some(l) = level {
+        rules.push(Rule { prefix: prefix.to_string(), packed: level::pack(l), runtime: true });
+    }
+    VERSION.fetch_add(1, Ordering::AcqRel);
+}
+
+/// Builds rules for `table`; when a name appears twice the later entry wins.
+/// Rules whose level is unchanged keep their generation, so a logger's own
+/// `set_level` since then still overrides them.
+fn build(current: &[Rule], table: Vec<(String, LogLevel)>, runtime: bool) -> Vec<Rule> {
+    let mut rules: Vec<Rule> = Vec::new();
+    for (prefix, l) in table {
+        rules.retain(|r| r.prefix != prefix);
+        let packed = match current.iter().find(|r| r.prefix == prefix) {
+            Some(r) if r.packed & 0xff == l.number() as u64 => r.packed,
+            _ => level::pack(l),
+        };
+        rules.push(Rule { prefix, packed, runtime });
+    }
+    rules
+}
+
+/// Replaces the whole table.
+pub fn replace(table: Vec<(String, LogLevel)>) {
+    let mut current = RULES.write().unwrap();
+    *current = build(&current, table, true);
+    VERSION.fetch_add(1, Ordering::AcqRel);
+}
+
+/// Replaces the rules a configuration file set. Rules set from code stay and
+/// win over the file's for the same name.
+pub fn configure(table: Vec<(String, LogLevel)>) {
+    let mut current = RULES.write().unwrap();
+    let mut rules = build(&current, table, false);
+    for r in current.drain(..).filter(|r| r.runtime) {
+        rules.retain(|f| f.prefix != r.prefix);
+        rules.push(r);
+    }
+    *current = rules;
+    VERSION.fetch_add(1, Ordering::AcqRel);
+}
+
+/// Parses `name=LEVEL` pairs separated by commas.
+pub fn parse(spec: &str) -> Result<Vec<(String, LogLevel)>, String> {
+    let mut table = Vec::new();
+    for entry in spec.split(',').map(str::trim).filter(|e| !e.is_empty()) {
+        let (name, lvl) = entry
+            .split_once('=')
+            .ok_or_else(|| format!("expected name=LEVEL, got {:?}", entry))?;
+        let lvl = LogLevel::parse(lvl).ok_or_else(|| format!("unknown log level in {:?}", entry))?;
+        table.push((name.trim().to_string(), lvl));
+    }
+    Ok(table)
+}
+
+/// Accepts either a spec string or a `{name: level}` dict from Python.
+pub fn extract(rules: &Bound<'_, PyAny>) -> PyResult<Vec<(String, LogLevel)>> {
+    if let Ok(d) = rules.downcast::<PyDict>() {
+        return d
+            .iter()
+            .map(|(k, v)| Ok((k.extract::<String>()?, LogLevel::extract(&v)?)))
+            .collect();
+    }
+    parse(&rules.extract::<String>()?).map_err(PyValueError::new_err)
+}
+
+/// Rules from `FASTLOGGER_LEVELS`, empty if it is unset.
+pub fn env_table() -> PyResult<Vec<(String, LogLevel)>> {
+    match std::env::var(ENV_VAR) {
+        Ok(spec) => parse(&spec).map_err(|e| PyValueError::new_err(format!("{}: {}", ENV_VAR, e))),
+        Err(_) => Ok(Vec::new()),
+    }
+}
+
+pub fn load_env() -> PyResult<()> {
+    let table = env_table()?;
+    if !table.is_empty() {
+        replace(table);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::level::LevelCell;
+    use std::sync::Mutex;
+
+    // The table is global; tests that replace it take turns.
+    static SERIAL: Mutex<()> = Mutex::new(());
+
+    #[test]
+    fn set_level_outlives_unrelated_table_changes() {
+        let _turn = SERIAL.lock().unwrap();
+        replace(vec![("gen_a".into(), LogLevel::WARNING), ("gen_b".into(), LogLevel::WARNING)]);
+        let cell = LevelCell::new("gen_a.worker", LogLevel::INFO);
+        assert!(cell.get() == LogLevel::WARNING);
+        cell.set(LogLevel::DEBUG);
+        assert!(cell.get() == LogLevel::DEBUG);
+        // Only gen_b changes: the logger's own, newer level still wins.
+        replace(vec![("gen_a".into(), LogLevel::WARNING), ("gen_b".into(), LogLevel::ERROR)]);
+        assert!(cell.get() == LogLevel::DEBUG);
+        // A changed rule for gen_a is newer than set_level.
+        replace(vec![("gen_a".into(), LogLevel::ERROR)]);
+        assert!(cell.get() == LogLevel::ERROR);
+        cell.set(LogLevel::INFO);
+        assert!(cell.get() == LogLevel::INFO);
+        replace(Vec::new());
+    }
+
+    #[test]
+    fn configure_keeps_rules_set_from_code() {
+        let _turn = SERIAL.lock().unwrap();
+        let level = |name| LogLevel::from_number((lookup(name) & 0xff) as i64).name();
+        set("cfg_a", Some(LogLevel::DEBUG));
+        configure(vec![("cfg_a".into(), LogLevel::ERROR), ("cfg_b".into(), LogLevel::WARNING)]);
+        assert_eq!((level("cfg_a"), level("cfg_b")), ("DEBUG", "WARNING"));
+        configure(vec![("cfg_b".into(), LogLevel::ERROR)]);
+        assert_eq!((level("cfg_a"), level("cfg_b")), ("DEBUG", "ERROR"));
+        // Removing the rule from code hands the name back to the file.
+        set("cfg_a", None);
+        configure(vec![("cfg_a".into(), LogLevel::ERROR)]);
+        assert_eq!(level("cfg_a"), "ERROR");
+        replace(Vec::new());
+        assert_eq!(lookup("cfg_a"), 0);
+    }
+}
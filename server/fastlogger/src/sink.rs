@@ -0,0 +1,891 @@
+use chrono::{DateTime, Utc};
+use pyo3::exceptions::{PyTypeError, PyValueError};
+use pyo3::prelude::*;
+use pyo3::types::PyDict;
+use serde_json::Value;
+use std::collections::{HashMap, VecDeque};
+use std::io;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use crate::clock::{Clock, Precision, Zone};
+use crate::compress::Compression;
+use crate::config::{self, choice, FileSpec};
+use crate::console::{Color, Console};
+use crate::error::{Errors, OnError};
+use crate::fields::{self, Fields};
+use crate::exception::ExcInfo;
+use crate::format::{truncate, Entry, Format, Formatter, Newlines};
+use crate::journald::{self, Journald};
+use crate::level::LogLevel;
+use crate::otlp::{self, Endpoint, Otlp, OtlpSpec};
+use crate::rotation::Policy;
+use crate::syslog::{self, Protocol, Syslog, Transport};
+use crate::writer::{Overflow, Writer};
+
+/// The last `capacity` items, oldest first.
+pub struct Memory<T> {
+    capacity: usize,
+    items: Mutex<VecDeque<T>>,
+}
+
+impl<T> Memory<T> {
+    fn new(capacity: usize) -> Self {
+        Memory { capacity, items: Mutex::new(VecDeque::new()) }
+    }
+
+    fn push(&self, item: T) {
+        let mut items = self.items.lock().unwrap();
+        if items.len() == self.capacity {
+            items.pop_front();
+        }
+        if self.capacity > 0 {
+            items.push_back(item);
+        }
+    }
+}
+
+/// A record kept by a ring buffer sink.
+#[derive(Clone)]
+pub struct Kept {
+    at: DateTime<Utc>,
+    level: LogLevel,
+    label: String,
+    ts: String,
+    name: String,
+    msg: String,
+    fields: Fields,
+    exc: Option<Value>,
+}
+
+impl Kept {
+    fn to_dict<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyDict>> {
+        let d = PyDict::new(py);
+        d.set_item("timestamp", &self.ts)?;
+        d.set_item("level", &self.label)?;
+        d.set_item("logger", &self.name)?;
+        d.set_item("message", &self.msg)?;
+        d.set_item("fields", fields::to_python(py, &fields::to_object(&self.fields))?)?;
+        d.set_item("exception", self.exc.as_ref().map(|e| fields::to_python(py, e)).transpose()?)?;
+        Ok(d)
+    }
+}
+
+impl Memory<Kept> {
+    /// The newest `limit` records matching every filter, oldest first.
+    fn recent(
+        &self,
+        level: Option<LogLevel>,
+        name_prefix: Option<&str>,
+        since: Option<DateTime<Utc>>,
+        limit: usize,
+        fields: &Fields,
+    ) -> Vec<Kept> {
+        let items = self.items.lock().unwrap();
+        let mut found: Vec<Kept> = items
+            .iter()
+            .rev()
+            .filter(|k| level.is_none_or(|l| k.level >= l))
+            .filter(|k| name_prefix.is_none_or(|p| k.name.starts_with(p)))
+            .filter(|k| since.is_none_or(|t| k.at >= t))
+            .filter(|k| fields.iter().all(|f| k.fields.contains(f)))
+            .take(limit)
+            .cloned()
+            .collect();
+        found.reverse();
+        found
+    }
+}
+
+pub enum Target {
+    File(Arc<Writer>),
+    Console(Console),
+    Memory(Memory<String>),
+    Ring(Memory<Kept>),
+    Syslog(Syslog),
+    Journald(Journald),
+    Otlp(Otlp),
+}
+
+/// One destination of a logger, with its own threshold and line format.
+pub struct Sink {
+    pub level: Option<LogLevel>,
+    pub formatter: Formatter,
+    pub target: Target,
+}
+
+impl Sink {
+    pub fn accepts(&self, level: LogLevel) -> bool {
+        self.level.is_none_or(|min| level >= min)
+    }
+
+    /// True if the sink wants to know which code logged a record.
+    pub fn records_code(&self) -> bool {
+        matches!(self.target, Target::Journald(_) | Target::Otlp(_))
+    }
+
+    /// Renders `entry` with this sink's formatter and writes it.
+    pub fn emit(&self, py: Python<'_>, level: LogLevel, now: DateTime<Utc>, entry: &Entry) -> PyResult<()> {
+        match &self.target {
+            Target::Console(c) => c.write(py, level, &self.formatter.render(now, entry)),
+            Target::Otlp(o) => {
+                // Exceptions go in `exception.*` attributes rather than the body.
+                let line = self.formatter.render(now, &Entry { exc: None, ..*entry });
+                let record = otlp::record(level, now, line.trim_end_matches('\n'), entry, otlp::current_span(py));
+                let line = o.fallback.as_ref().filter(|f| f.accepts(level)).map(|f| f.formatter.render(now, entry));
+                let item = otlp::Item { scope: entry.name.to_string(), record, line };
+                o.exporter.send(item).map_err(|e| o.exporter.errors.to_py(e))
+            }
+            Target::Memory(_) | Target::Ring(_) => self.deliver(level, now, entry).map_err(|e| self.to_py(e)),
+            _ => py.allow_threads(|| self.deliver(level, now, entry)).map_err(|e| self.to_py(e)),
+        }
+    }
+
+    /// The part of `emit` that needs no Python: renders `entry` and writes
+    /// it to any target but a console or an OTLP exporter.
+    fn deliver(&self, level: LogLevel, now: DateTime<Utc>, entry: &Entry) -> io::Result<()> {
+        if let Target::Ring(r) = &self.target {
+            r.push(Kept {
+                at: now,
+                level,
+                label: entry.level.to_string(),
+                ts: self.formatter.clock.stamp(now).1,
+                name: entry.name.to_string(),
+                msg: truncate(entry.msg, self.formatter.max_message_bytes).into_owned(),
+                fields: entry.fields.clone(),
+                exc: entry.exc.map(ExcInfo::to_value),
+            });
+            return Ok(());
+        }
+        let line = self.formatter.render(now, entry);
+        match &self.target {
+            Target::File(w) => w.write(line),
+            Target::Memory(m) => {
+                m.push(line);
+                Ok(())
+            }
+            Target::Syslog(s) => s.send(level, entry.name, now, line.trim_end_matches('\n')),
+            Target::Journald(j) => j.send(level, entry.name, line.trim_end_matches('\n'), entry.code, entry.fields),
+            Target::Console(_) | Target::Ring(_) | Target::Otlp(_) => Ok(()),
+        }
+    }
+
+    fn to_py(&self, e: io::Error) -> PyErr {
+        match self.errors() {
+            Some(errors) => errors.to_py(e),
+            None => e.into(),
+        }
+    }
+
+    pub fn flush(&self, py: Python<'_>) -> PyResult<()> {
+        match &self.target {
+            Target::File(w) => py.allow_threads(|| w.flush()).map_err(|e| w.errors().to_py(e)),
+            Target::Otlp(o) => {
+                py.allow_threads(|| o.exporter.flush()).map_err(|e| o.exporter.errors.to_py(e))?;
+                o.fallback.as_ref().map_or(Ok(()), |f| f.flush(py))
+            }
+            Target::Syslog(s) => py.allow_threads(|| s.flush()).map_err(|e| s.errors.to_py(e)),
+            Target::Console(_) | Target::Memory(_) | Target::Ring(_) | Target::Journald(_) => Ok(()),
+        }
+    }
+
+    pub fn errors(&self) -> Option<&Errors> {
+        match &self.target {
+            Target::File(w) => Some(w.errors()),
+            Target::Syslog(s) => Some(&s.errors),
+            Target::Journald(j) => Some(&j.errors),
+            Target::Otlp(o) => Some(&o.exporter.errors),
+            _ => None,
+        }
+    }
+
+    /// Records discarded because a background queue was full.
+    pub fn dropped(&self) -> u64 {
+        match &self.target {
+            Target::File(w) => w.dropped(),
+            Target::Syslog(s) => s.dropped(),
+            Target::Otlp(o) => o.exporter.dropped(),
+            _ => 0,
+        }
+    }
+
+    pub fn writer(&self) -> Option<&Arc<Writer>> {
+        match &self.target {
+            Target::File(w) => Some(w),
+            _ => None,
+        }
+    }
+}
+
+/// A sink before it is opened.
+pub enum Kind {
+    File(FileSpec),
+    Console(Console),
+    Memory(usize),
+    Ring(usize),
+    Syslog(Syslog),
+    Journald(Journald),
+    Otlp(OtlpSpec),
+}
+
+pub struct SinkSpec {
+    pub kind: Kind,
+    pub level: Option<LogLevel>,
+    pub formatter: Formatter,
+}
+
+impl SinkSpec {
+    /// Opens the sink; `replace` is passed on to `FileSpec::open`.
+    pub fn open(self, replace: bool) -> PyResult<Arc<Sink>> {
+        let target = match self.kind {
+            Kind::File(spec) => Target::File(spec.open(replace)?),
+            Kind::Console(c) => Target::Console(c),
+            Kind::Memory(capacity) => Target::Memory(Memory::new(capacity)),
+            Kind::Ring(capacity) => Target::Ring(Memory::new(capacity)),
+            Kind::Syslog(s) => Target::Syslog(s),
+            Kind::Journald(j) => Target::Journald(j),
+            Kind::Otlp(o) => Target::Otlp(o.start()),
+        };
+        Ok(Arc::new(Sink { level: self.level, formatter: self.formatter, target }))
+    }
+}
+
+/// Builds a formatter from the constructor arguments every sink shares.
+pub fn formatter(
+    format: &str,
+    fmt: Option<&str>,
+    timezone: &str,
+    precision: &str,
+    newlines: &str,
+    max_message_bytes: Option<usize>,
+) -> PyResult<Formatter> {
+    let format = choice("log format", format, Format::parse)?
+        .with_template(fmt)
+        .map_err(PyValueError::new_err)?;
+    let zone = choice("timezone", timezone, Zone::parse)?;
+    let clock = Clock::new(zone, choice("precision", precision, Precision::parse)?);
+    let mut formatter = Formatter::new(format, clock);
+    formatter.newlines = choice("newline mode", newlines, Newlines::parse)?;
+    formatter.max_message_bytes = max_message_bytes;
+    Ok(formatter)
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn file_spec(
+    path: String,
+    max_bytes: u64,
+    backup_count: usize,
+    when: &str,
+    max_age_days: Option<f64>,
+    compress: Option<&str>,
+    background: bool,
+    queue_size: usize,
+    overflow: &str,
+    on_error: &str,
+) -> PyResult<FileSpec> {
+    let mut spec = FileSpec::new(path);
+    spec.rotation.max_bytes = max_bytes;
+    spec.rotation.backup_count = backup_count;
+    spec.rotation.policy = choice("rotation policy", when, Policy::parse)?;
+    spec.rotation.max_age = max_age_days.map(config::days).transpose()?;
+    spec.rotation.compression = compress.map(|c| choice("compression", c, Compression::parse)).transpose()?;
+    spec.background = background;
+    spec.queue_size = queue_size;
+    spec.overflow = choice("overflow policy", overflow, Overflow::parse)?;
+    spec.on_error = choice("on_error policy", on_error, OnError::parse)?;
+    Ok(spec)
+}
+
+fn min_level(level: Option<&Bound<'_, PyAny>>) -> PyResult<Option<LogLevel>> {
+    level.map(LogLevel::extract).transpose()
+}
+
+/// Writes to a rotating log file; sinks for the same path share one writer.
+#[pyclass(frozen, module = "fastlogger")]
+pub struct FileSink {
+    sink: Arc<Sink>,
+}
+
+#[pymethods]
+impl FileSink {
+    #[new]
+    #[allow(clippy::too_many_arguments)]
+    #[pyo3(signature = (
+        path, level=None, format="text", fmt=None, timezone="utc", precision="ms", newlines="indent",
+        max_message_bytes=None, max_bytes=10 * 1024 * 1024, backup_count=5, when="size", max_age_days=None,
+        compress=None, background=false, queue_size=10000, overflow="block", on_error="raise"
+    ))]
+    fn new(
+        path: String,
+        level: Option<&Bound<'_, PyAny>>,
+        format: &str,
+        fmt: Option<&str>,
+        timezone: &str,
+        precision: &str,
+        newlines: &str,
+        max_message_bytes: Option<usize>,
+        max_bytes: u64,
+        backup_count: usize,
+        when: &str,
+        max_age_days: Option<f64>,
+        compress: Option<&str>,
+        background: bool,
+        queue_size: usize,
+        overflow: &str,
+        on_error: &str,
+    ) -> PyResult<Self> {
+        let spec = SinkSpec {
+            kind: Kind::File(file_spec(
+                path, max_bytes, backup_count, when, max_age_days, compress, background, queue_size, overflow, on_error,
+            )?),
+            level: min_level(level)?,
+            formatter: formatter(format, fmt, timezone, precision, newlines, max_message_bytes)?,
+        };
+        Ok(FileSink { sink: spec.open(false)? })
+    }
+}
+
+/// Writes to Python's `sys.stdout` (below WARNING) and `sys.stderr`.
+#[pyclass(frozen, module = "fastlogger")]
+pub struct ConsoleSink {
+    sink: Arc<Sink>,
+}
+
+#[pymethods]
+impl ConsoleSink {
+    #[new]
+    #[allow(clippy::too_many_arguments)]
+    #[pyo3(signature = (
+        level=None, format="text", fmt=None, timezone="utc", precision="ms", newlines="indent",
+        max_message_bytes=None, color="auto", stdout=None, stderr=None
+    ))]
+    fn new(
+        level: Option<&Bound<'_, PyAny>>,
+        format: &str,
+        fmt: Option<&str>,
+        timezone: &str,
+        precision: &str,
+        newlines: &str,
+        max_message_bytes: Option<usize>,
+        color: &str,
+        stdout: Option<Py<PyAny>>,
+        stderr: Option<Py<PyAny>>,
+    ) -> PyResult<Self> {
+        let color = choice("color mode", color, Color::parse)?;
+        let spec = SinkSpec {
+            kind: Kind::Console(Console { stdout, stderr, color }),
+            level: min_level(level)?,
+            formatter: formatter(format, fmt, timezone, precision, newlines, max_message_bytes)?,
+        };
+        Ok(ConsoleSink { sink: spec.open(false)? })
+    }
+}
+
+/// Keeps the last `capacity` rendered lines in memory.
+#[pyclass(frozen, module = "fastlogger")]
+pub struct MemorySink {
+    sink: Arc<Sink>,
+}
+
+impl MemorySink {
+    fn memory(&self) -> &Memory<String> {
+        match &self.sink.target {
+            Target::Memory(m) => m,
+            _ => unreachable!("MemorySink wraps a memory target"),
+        }
+    }
+}
+
+#[pymethods]
+impl MemorySink {
+    #[new]
+    #[allow(clippy::too_many_arguments)]
+    #[pyo3(signature = (
+        capacity=1000, level=None, format="text", fmt=None, timezone="utc", precision="ms", newlines="indent",
+        max_message_bytes=None
+    ))]
+    fn new(
+        capacity: usize,
+        level: Option<&Bound<'_, PyAny>>,
+        format: &str,
+        fmt: Option<&str>,
+        timezone: &str,
+        precision: &str,
+        newlines: &str,
+        max_message_bytes: Option<usize>,
+    ) -> PyResult<Self> {
+        let spec = SinkSpec {
+            kind: Kind::Memory(capacity),
+            level: min_level(level)?,
+            formatter: formatter(format, fmt, timezone, precision, newlines, max_message_bytes)?,
+        };
+        Ok(MemorySink { sink: spec.open(false)? })
+    }
+
+    /// The buffered lines, oldest first.
+    fn lines(&self) -> Vec<String> {
+        self.memory().items.lock().unwrap().iter().cloned().collect()
+    }
+
+    fn clear(&self) {
+        self.memory().items.lock().unwrap().clear();
+    }
+}
+
+/// Keeps the last `capacity` records, structured, for `recent()` queries
+/// such as a debug page.
+#[pyclass(frozen, module = "fastlogger")]
+pub struct RingBufferSink {
+    sink: Arc<Sink>,
+}
+
+impl RingBufferSink {
+    fn ring(&self) -> &Memory<Kept> {
+        match &self.sink.target {
+            Target::Ring(r) => r,
+            _ => unreachable!("RingBufferSink wraps a ring target"),
+        }
+    }
+}
+
+/// Reads `since` as a `datetime` or Unix timestamp. A naive `datetime` is
+/// taken as UTC, like the timestamps the ring keeps, not as local time.
+fn since_time(since: &Bound<'_, PyAny>) -> PyResult<DateTime<Utc>> {
+    let py = since.py();
+    let secs: f64 = match since.hasattr("timestamp")? {
+        true if since.call_method0("utcoffset")?.is_none() => {
+            let kwargs = PyDict::new(py);
+            kwargs.set_item("tzinfo", py.import("datetime")?.getattr("timezone")?.getattr("utc")?)?;
+            since.call_method("replace", (), Some(&kwargs))?.call_method0("timestamp")?.extract()?
+        }
+        true => since.call_method0("timestamp")?.extract()?,
+        false => since.extract()?,
+    };
+    DateTime::from_timestamp_micros((secs * 1e6) as i64)
+        .ok_or_else(|| PyValueError::new_err(format!("since out of range: {}", secs)))
+}
+
+#[pymethods]
+impl RingBufferSink {
+    #[new]
+    #[pyo3(signature = (capacity=1000, level=None, timezone="utc", precision="ms", max_message_bytes=None))]
+    fn new(
+        capacity: usize,
+        level: Option<&Bound<'_, PyAny>>,
+        timezone: &str,
+        precision: &str,
+        max_message_bytes: Option<usize>,
+    ) -> PyResult<Self> {
+        let spec = SinkSpec {
+            kind: Kind::Ring(capacity),
+            level: min_level(level)?,
+            formatter: formatter("text", None, timezone, precision, "indent", max_message_bytes)?,
+        };
+        Ok(RingBufferSink { sink: spec.open(false)? })
+    }
+
+    /// The newest `limit` records at or above `level` from loggers whose
+    /// name starts with `name_prefix`, logged at or after `since`, oldest
+    /// first. Keyword arguments match field values, e.g. `request_id=`.
+    #[pyo3(signature = (level=None, name_prefix=None, since=None, limit=100, **fields))]
+    fn recent<'py>(
+        &self,
+        py: Python<'py>,
+        level: Option<&Bound<'_, PyAny>>,
+        name_prefix: Option<&str>,
+        since: Option<&Bound<'_, PyAny>>,
+        limit: usize,
+        fields: Option<&Bound<'_, PyDict>>,
+    ) -> PyResult<Vec<Bound<'py, PyDict>>> {
+        let level = min_level(level)?;
+        let since = since.map(since_time).transpose()?;
+        let fields = fields::from_kwargs(fields)?;
+        let found = self.ring().recent(level, name_prefix, since, limit, &fields);
+        found.iter().map(|k| k.to_dict(py)).collect()
+    }
+
+    fn clear(&self) {
+        self.ring().items.lock().unwrap().clear();
+    }
+
+    fn __len__(&self) -> usize {
+        self.ring().items.lock().unwrap().len()
+    }
+}
+
+/// Builds a syslog client; the transport follows from `address` unless given.
+pub fn syslog(
+    address: String,
+    transport: Option<&str>,
+    protocol: &str,
+    facility: &str,
+    app_name: Option<String>,
+    on_error: &str,
+) -> PyResult<Syslog> {
+    let transport = match transport {
+        Some(t) => choice("syslog transport", t, Transport::parse)?,
+        None => Transport::infer(&address),
+    };
+    let errors = Errors::new(&address, choice("on_error policy", on_error, OnError::parse)?);
+    let mut s = Syslog::new(
+        address,
+        transport,
+        choice("syslog protocol", protocol, Protocol::parse)?,
+        choice("syslog facility", facility, syslog::facility)?,
+        errors,
+    );
+    s.app_name = app_name;
+    Ok(s)
+}
+
+/// Syslog carries its own timestamp, host and app name, so by default only
+/// the message and fields go in the body.
+pub const SYSLOG_FMT: &str = "{message} {fields}";
+
+/// Sends records to a syslog daemon over a Unix socket, UDP or TCP.
+#[pyclass(frozen, module = "fastlogger")]
+pub struct SyslogSink {
+    sink: Arc<Sink>,
+}
+
+#[pymethods]
+impl SyslogSink {
+    #[new]
+    #[allow(clippy::too_many_arguments)]
+    #[pyo3(signature = (
+        address="/dev/log".to_string(), transport=None, protocol="rfc5424", facility="user", app_name=None,
+        level=None, format="text", fmt=None, timezone="utc", precision="ms", newlines="escape",
+        max_message_bytes=None, on_error="raise"
+    ))]
+    fn new(
+        address: String,
+        transport: Option<&str>,
+        protocol: &str,
+        facility: &str,
+        app_name: Option<String>,
+        level: Option<&Bound<'_, PyAny>>,
+        format: &str,
+        fmt: Option<&str>,
+        timezone: &str,
+        precision: &str,
+        newlines: &str,
+        max_message_bytes: Option<usize>,
+        on_error: &str,
+    ) -> PyResult<Self> {
+        let fmt = fmt.or((format == "text").then_some(SYSLOG_FMT));
+        let spec = SinkSpec {
+            kind: Kind::Syslog(syslog(address, transport, protocol, facility, app_name, on_error)?),
+            level: min_level(level)?,
+            formatter: formatter(format, fmt, timezone, precision, newlines, max_message_bytes)?,
+        };
+        Ok(SyslogSink { sink: spec.open(false)? })
+    }
+}
+
+/// Opens the client side of journald's socket at `path`.
+pub fn journald(path: String, identifier: Option<String>, on_error: OnError) -> PyResult<Journald> {
+    let errors = Errors::new(&path, on_error);
+    let mut j = Journald::new(path.clone(), errors).map_err(|e| crate::error::to_py(&path, e))?;
+    j.identifier = identifier;
+    Ok(j)
+}
+
+/// Fields travel as journal fields, so the message is sent on its own.
+pub const JOURNALD_FMT: &str = "{message}";
+
+/// Sends records to systemd-journald with `LOGGER`, `PRIORITY`, `CODE_*`
+/// and every bound or context field as journal fields.
+#[pyclass(frozen, module = "fastlogger")]
+pub struct JournaldSink {
+    sink: Arc<Sink>,
+}
+
+#[pymethods]
+impl JournaldSink {
+    #[new]
+    #[allow(clippy::too_many_arguments)]
+    #[pyo3(signature = (
+        path=journald::SOCKET.to_string(), identifier=None, level=None, format="text", fmt=None, timezone="utc",
+        precision="ms", newlines="raw", max_message_bytes=None, on_error="raise"
+    ))]
+    fn new(
+        path: String,
+        identifier: Option<String>,
+        level: Option<&Bound<'_, PyAny>>,
+        format: &str,
+        fmt: Option<&str>,
+        timezone: &str,
+        precision: &str,
+        newlines: &str,
+        max_message_bytes: Option<usize>,
+        on_error: &str,
+    ) -> PyResult<Self> {
+        let fmt = fmt.or((format == "text").then_some(JOURNALD_FMT));
+        let spec = SinkSpec {
+            kind: Kind::Journald(journald(path, identifier, choice("on_error policy", on_error, OnError::parse)?)?),
+            level: min_level(level)?,
+            formatter: formatter(format, fmt, timezone, precision, newlines, max_message_bytes)?,
+        };
+        Ok(JournaldSink { sink: spec.open(false)? })
+    }
+}
+
+/// Builds exporter settings from the arguments shared with the config file.
+#[allow(clippy::too_many_arguments)]
+pub fn otlp_settings(
+    endpoint: &str,
+    service_name: &str,
+    resource: Fields,
+    headers: Vec<(String, String)>,
+    batch_size: usize,
+    flush_interval: f64,
+    max_retries: u32,
+    retry_backoff: f64,
+    timeout: f64,
+    queue_size: usize,
+) -> PyResult<otlp::Settings> {
+    let endpoint = Endpoint::parse(endpoint).map_err(PyValueError::new_err)?;
+    let seconds = |what: &str, s: f64| {
+        Duration::try_from_secs_f64(s).map_err(|_| PyValueError::new_err(format!("invalid {} {}", what, s)))
+    };
+    for (k, v) in &headers {
+        otlp::check_header(k, v).map_err(PyValueError::new_err)?;
+    }
+    let mut settings = otlp::Settings::new(endpoint, service_name);
+    fields::merge(&mut settings.resource, resource);
+    settings.headers = headers;
+    settings.batch_size = batch_size.max(1);
+    settings.flush_interval = seconds("flush_interval", flush_interval)?;
+    settings.max_retries = max_retries;
+    settings.retry_backoff = seconds("retry_backoff", retry_backoff)?;
+    settings.timeout = seconds("timeout", timeout)?;
+    settings.queue_size = queue_size;
+    Ok(settings)
+}
+
+/// OTLP carries fields, code location and exceptions as attributes, so by
+/// default only the message goes in the body.
+pub const OTLP_FMT: &str = "{message}";
+
+/// Exports records to an OpenTelemetry collector over OTLP/HTTP (JSON),
+/// batching them on a background thread. While the collector is down,
+/// records go to the `fallback` file sink instead.
+#[pyclass(frozen, module = "fastlogger")]
+pub struct OtlpSink {
+    sink: Arc<Sink>,
+}
+
+#[pymethods]
+impl OtlpSink {
+    #[new]
+    #[allow(clippy::too_many_arguments)]
+    #[pyo3(signature = (
+        endpoint=otlp::ENDPOINT, service_name=otlp::SERVICE_NAME, resource=None, headers=None, fallback=None,
+        batch_size=512, flush_interval=1.0, max_retries=3, retry_backoff=0.5, timeout=5.0, queue_size=10000,
+        level=None, format="text", fmt=None, timezone="utc", precision="ms", newlines="indent",
+        max_message_bytes=None, on_error="raise"
+    ))]
+    fn new(
+        endpoint: &str,
+        service_name: &str,
+        resource: Option<&Bound<'_, PyDict>>,
+        headers: Option<HashMap<String, String>>,
+        fallback: Option<&Bound<'_, FileSink>>,
+        batch_size: usize,
+        flush_interval: f64,
+        max_retries: u32,
+        retry_backoff: f64,
+        timeout: f64,
+        queue_size: usize,
+        level: Option<&Bound<'_, PyAny>>,
+        format: &str,
+        fmt: Option<&str>,
+        timezone: &str,
+        precision: &str,
+        newlines: &str,
+        max_message_bytes: Option<usize>,
+        on_error: &str,
+    ) -> PyResult<Self> {
+        let settings = otlp_settings(
+            endpoint,
+            service_name,
+            fields::from_kwargs(resource)?,
+            headers.unwrap_or_default().into_iter().collect(),
+            batch_size,
+            flush_interval,
+            max_retries,
+            retry_backoff,
+            timeout,
+            queue_size,
+        )?;
+        let fmt = fmt.or((format == "text").then_some(OTLP_FMT));
+        let spec = SinkSpec {
+            kind: Kind::Otlp(OtlpSpec {
+                settings,
+                on_error: choice("on_error policy", on_error, OnError::parse)?,
+                fallback: fallback.map(|f| f.get().sink.clone()),
+                fallback_name: None,
+            }),
+            level: min_level(level)?,
+            formatter: formatter(format, fmt, timezone, precision, newlines, max_message_bytes)?,
+        };
+        Ok(OtlpSink { sink: spec.open(false)? })
+    }
+}
+
+/// Unwraps any of the sink classes.
+pub fn extract(obj: &Bound<'_, PyAny>) -> PyResult<Arc<Sink>> {
+    if let Ok(s) = obj.downcast::<FileSink>() {
+        return Ok(s.get().sink.clone());
+    }
+    if let Ok(s) = obj.downcast::<ConsoleSink>() {
+        return Ok(s.get().sink.clone());
+    }
+    if let Ok(s) = obj.downcast::<MemorySink>() {
+        return Ok(s.get().sink.clone());
+    }
+    if let Ok(s) = obj.downcast::<RingBufferSink>() {
+        return Ok(s.get().sink.clone());
+    }
+    if let Ok(s) = obj.downcast::<SyslogSink>() {
+        return Ok(s.get().sink.clone());
+    }
+    if let Ok(s) = obj.downcast::<JournaldSink>() {
+        return Ok(s.get().sink.clone());
+    }
+    if let Ok(s) = obj.downcast::<OtlpSink>() {
+        return Ok(s.get().sink.clone());
+    }
+    Err(PyTypeError::new_err(format!("expected a sink, not {}", obj.get_type().name()?)))
+}
+
+/// Wraps `sink` in the Python class for its kind.
+pub fn wrap(py: Python<'_>, sink: &Arc<Sink>) -> PyResult<Py<PyAny>> {
+    let sink = sink.clone();
+    Ok(match sink.target {
+        Target::File(_) => Py::new(py, FileSink { sink })?.into_any(),
+        Target::Console(_) => Py::new(py, ConsoleSink { sink })?.into_any(),
+        Target::Memory(_) => Py::new(py, MemorySink { sink })?.into_any(),
+        Target::Ring(_) => Py::new(py, RingBufferSink { sink })?.into_any(),
+        Target::Syslog(_) => Py::new(py, SyslogSink { sink })?.into_any(),
+        Target::Journald(_) => Py::new(py, JournaldSink { sink })?.into_any(),
+        Target::Otlp(_) => Py::new(py, OtlpSink { sink })?.into_any(),
+    })
+}
+
+pub fn register(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add_class::<FileSink>()?;
+    m.add_class::<ConsoleSink>()?;
+    m.add_class::<MemorySink>()?;
+    m.add_class::<RingBufferSink>()?;
+    m.add_class::<SyslogSink>()?;
+    m.add_class::<JournaldSink>()?;
+    m.add_class::<OtlpSink>()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::template::Template;
+    use std::mem::ManuallyDrop;
+    use std::os::unix::net::UnixDatagram;
+
+    /// Never dropped, not even on a panic: a `Sink`'s drop glue covers the
+    /// console's Python streams, which unit tests cannot link.
+    fn sink(target: Target, fmt: &str, newlines: Newlines) -> ManuallyDrop<Sink> {
+        let target = ManuallyDrop::new(target);
+        let format = Format::Template(Arc::new(Template::compile(fmt).unwrap()));
+        let mut formatter = Formatter::new(format, Clock::new(Zone::Utc, Precision::Millis));
+        formatter.newlines = newlines;
+        ManuallyDrop::new(Sink { level: None, formatter, target: ManuallyDrop::into_inner(target) })
+    }
+
+    fn entry<'a>(msg: &'a str, fields: &'a Fields) -> Entry<'a> {
+        Entry { level: "WARNING", name: "routes.chat", msg, fields, exc: None, code: None }
+    }
+
+    /// A ring holding `(level, logger, message, request_id)` records logged
+    /// one second apart from `t0`.
+    fn ring(capacity: usize, records: &[(LogLevel, &str, &str, &str)]) -> (ManuallyDrop<Sink>, DateTime<Utc>) {
+        let t0 = DateTime::parse_from_rfc3339("2026-10-16T12:00:00Z").unwrap().with_timezone(&Utc);
+        let s = sink(Target::Ring(Memory::new(capacity)), "{message}", Newlines::Indent);
+        for (i, &(level, name, msg, request_id)) in records.iter().enumerate() {
+            let fields = vec![("request_id".to_string(), serde_json::json!(request_id))];
+            let e = Entry { level: level.name(), name, msg, fields: &fields, exc: None, code: None };
+            s.deliver(level, t0 + chrono::Duration::seconds(i as i64), &e).unwrap();
+        }
+        (s, t0)
+    }
+
+    fn kept(s: &Sink) -> &Memory<Kept> {
+        match &s.target {
+            Target::Ring(r) => r,
+            _ => unreachable!(),
+        }
+    }
+
+    fn messages(found: &[Kept]) -> Vec<&str> {
+        found.iter().map(|k| k.msg.as_str()).collect()
+    }
+
+    const RECORDS: &[(LogLevel, &str, &str, &str)] = &[
+        (LogLevel::INFO, "routes.chat", "GET /chat", "r-1"),
+        (LogLevel::DEBUG, "chat_service", "prompt built", "r-1"),
+        (LogLevel::WARNING, "chat_service.llm", "slow reply", "r-1"),
+        (LogLevel::ERROR, "routes.chat", "500", "r-2"),
+        (LogLevel::INFO, "chat_service", "reply sent", "r-2"),
+    ];
+
+    #[test]
+    fn ring_filters_oldest_first() {
+        let (s, t0) = ring(10, RECORDS);
+        let r = kept(&s);
+        let none = Fields::new();
+        assert_eq!(messages(&r.recent(None, None, None, 100, &none)).len(), 5);
+        assert_eq!(messages(&r.recent(Some(LogLevel::WARNING), None, None, 100, &none)), ["slow reply", "500"]);
+        assert_eq!(
+            messages(&r.recent(None, Some("chat_service"), None, 100, &none)),
+            ["prompt built", "slow reply", "reply sent"]
+        );
+        let since = t0 + chrono::Duration::seconds(3);
+        assert_eq!(messages(&r.recent(None, None, Some(since), 100, &none)), ["500", "reply sent"]);
+        // The limit keeps the newest matches, still oldest first.
+        assert_eq!(messages(&r.recent(None, None, None, 2, &none)), ["500", "reply sent"]);
+        let r1 = vec![("request_id".to_string(), serde_json::json!("r-1"))];
+        assert_eq!(messages(&r.recent(None, None, None, 100, &r1)), ["GET /chat", "prompt built", "slow reply"]);
+        assert_eq!(messages(&r.recent(Some(LogLevel::INFO), Some("routes"), None, 1, &r1)), ["GET /chat"]);
+        let unknown = vec![("request_id".to_string(), serde_json::json!("r-9"))];
+        assert!(r.recent(None, None, None, 100, &unknown).is_empty());
+    }
+
+    #[test]
+    fn ring_evicts_the_oldest() {
+        let (s, t0) = ring(3, RECORDS);
+        let r = kept(&s);
+        let all = r.recent(None, None, None, 100, &Fields::new());
+        assert_eq!(messages(&all), ["slow reply", "500", "reply sent"]);
+        let k = &all[0];
+        assert!(k.at == t0 + chrono::Duration::seconds(2));
+        assert_eq!((k.label.as_str(), k.name.as_str()), ("WARNING", "chat_service.llm"));
+        assert_eq!(k.ts, "2026-10-16T12:00:02.000Z");
+        let (empty, _) = ring(0, RECORDS);
+        assert!(kept(&empty).recent(None, None, None, 100, &Fields::new()).is_empty());
+    }
+
+    #[test]
+    fn journald_keeps_newlines_in_the_message() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("journal.sock");
+        let server = UnixDatagram::bind(&path).unwrap();
+        let path = path.to_string_lossy().to_string();
+        let j = Journald::new(path.clone(), Errors::new(&path, OnError::Raise)).unwrap();
+        let raw = Newlines::parse("raw").unwrap();
+        let s = sink(Target::Journald(j), JOURNALD_FMT, raw);
+        let msg = "slow reply\n  model=llama\n  ms=850";
+        let fields = Fields::new();
+        s.deliver(LogLevel::WARNING, Utc::now(), &entry(msg, &fields)).unwrap();
+        let mut buf = vec![0; 4096];
+        let n = server.recv(&mut buf).unwrap();
+        assert_eq!(&buf[..n], journald::encode(LogLevel::WARNING, "routes.chat", None, msg, None, &fields));
+    }
+}
@@ -0,0 +1,403 @@
+use chrono::{DateTime, Local, SecondsFormat, Utc};
+use std::io::{self, Write};
+use std::net::{SocketAddr, TcpStream, ToSocketAddrs, UdpSocket};
+use std::os::unix::net::UnixDatagram;
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::{Duration, Instant};
+
+use crate::error::Errors;
+use crate::level::LogLevel;
+use crate::writer::{Background, Lines, Overflow};
+
+const TIMEOUT: Duration = Duration::from_secs(5);
+const RETRY_AFTER: Duration = Duration::from_secs(1);
+/// Records a TCP sink holds while the daemon is slow; newer ones are dropped
+/// and counted beyond that.
+const QUEUE_SIZE: usize = 10000;
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Protocol {
+    Rfc5424,
+    Rfc3164,
+}
+
+impl Protocol {
+    pub fn parse(s: &str) -> Option<Self> {
+        match s.to_lowercase().as_str() {
+            "rfc5424" | "5424" => Some(Protocol::Rfc5424),
+            "rfc3164" | "3164" | "bsd" => Some(Protocol::Rfc3164),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Transport {
+    /// A local datagram socket such as `/dev/log`.
+    Unix,
+    Udp,
+    /// Octet-counted frames (RFC 6587).
+    Tcp,
+}
+
+impl Transport {
+    pub fn parse(s: &str) -> Option<Self> {
+        match s.to_lowercase().as_str() {
+            "unix" => Some(Transport::Unix),
+            "udp" => Some(Transport::Udp),
+            "tcp" => Some(Transport::Tcp),
+            _ => None,
+        }
+    }
+
+    /// Paths mean a Unix socket, anything else `host:port` over UDP.
+    pub fn infer(address: &str) -> Self {
+        if address.contains('/') { Transport::Unix } else { Transport::Udp }
+    }
+}
+
+const FACILITIES: &[(&str, u8)] = &[
+    ("kern", 0), ("user", 1), ("mail", 2), ("daemon", 3), ("auth", 4), ("syslog", 5), ("lpr", 6),
+    ("news", 7), ("uucp", 8), ("cron", 9), ("authpriv", 10), ("ftp", 11), ("local0", 16), ("local1", 17),
+    ("local2", 18), ("local3", 19), ("local4", 20), ("local5", 21), ("local6", 22), ("local7", 23),
+];
+
+pub fn facility(s: &str) -> Option<u8> {
+    FACILITIES.iter().find(|(n, _)| n.eq_ignore_ascii_case(s)).map(|&(_, code)| code)
+}
+
+pub fn severity(level: LogLevel) -> u8 {
+    match level {
+        LogLevel::TRACE | LogLevel::DEBUG => 7,
+        LogLevel::INFO => 6,
+        LogLevel::WARNING => 4,
+        LogLevel::ERROR => 3,
+        LogLevel::CRITICAL => 2,
+    }
+}
+
+fn hostname() -> &'static str {
+    static HOST: OnceLock<String> = OnceLock::new();
+    HOST.get_or_init(|| {
+        let name = std::fs::read_to_string("/proc/sys/kernel/hostname")
+            .or_else(|_| std::env::var("HOSTNAME"))
+            .unwrap_or_default();
+        let name = name.trim();
+        if name.is_empty() { "-".into() } else { name.into() }
+    })
+}
+
+/// Printable ASCII only, as both RFCs require of header fields.
+fn header_field(s: &str, max: usize) -> String {
+    let s: String = s.chars().take(max).map(|c| if c.is_ascii_graphic() { c } else { '_' }).collect();
+    if s.is_empty() { "-".into() } else { s }
+}
+
+/// Builds one syslog message, without transport framing.
+fn message(protocol: Protocol, pri: u8, now: DateTime<Utc>, app: &str, msg: &str) -> String {
+    let pid = std::process::id();
+    match protocol {
+        Protocol::Rfc5424 => format!(
+            "<{}>1 {} {} {} {} - - {}",
+            pri,
+            now.to_rfc3339_opts(SecondsFormat::Micros, true),
+            header_field(hostname(), 255),
+            header_field(app, 48),
+            pid,
+            msg
+        ),
+        Protocol::Rfc3164 => format!(
+            "<{}>{} {} {}[{}]: {}",
+            pri,
+            now.with_timezone(&Local).format("%b %e %H:%M:%S"),
+            header_field(hostname(), 255),
+            header_field(app, 32),
+            pid,
+            msg
+        ),
+    }
+}
+
+enum Conn {
+    Unix(UnixDatagram),
+    Udp(UdpSocket),
+    Tcp(TcpStream),
+}
+
+fn resolve(address: &str) -> io::Result<SocketAddr> {
+    address
+        .to_socket_addrs()?
+        .next()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, format!("cannot resolve {}", address)))
+}
+
+/// A connection to the daemon, reopened after failures.
+struct Link {
+    address: String,
+    transport: Transport,
+    conn: Option<Conn>,
+    /// After a failed connect, sends fail fast until then instead of each
+    /// waiting out `TIMEOUT`.
+    down_until: Option<Instant>,
+}
+
+impl Link {
+    fn connect(&mut self) -> io::Result<Conn> {
+        if self.down_until.is_some_and(|t| Instant::now() < t) {
+            return Err(io::Error::new(io::ErrorKind::NotConnected, format!("{} is unreachable", self.address)));
+        }
+        let conn = match self.transport {
+            Transport::Unix => UnixDatagram::unbound().and_then(|s| s.connect(&self.address).map(|_| Conn::Unix(s))),
+            Transport::Udp => resolve(&self.address).and_then(|addr| {
+                let s = UdpSocket::bind(if addr.is_ipv4() { "0.0.0.0:0" } else { "[::]:0" })?;
+                s.connect(addr)?;
+                Ok(Conn::Udp(s))
+            }),
+            Transport::Tcp => resolve(&self.address).and_then(|addr| {
+                let s = TcpStream::connect_timeout(&addr, TIMEOUT)?;
+                s.set_write_timeout(Some(TIMEOUT))?;
+                Ok(Conn::Tcp(s))
+            }),
+        };
+        if conn.is_err() && self.transport == Transport::Tcp {
+            self.down_until = Some(Instant::now() + RETRY_AFTER);
+        }
+        conn
+    }
+
+    fn send_on(conn: &mut Conn, msg: &str) -> io::Result<()> {
+        match conn {
+            Conn::Unix(s) => s.send(msg.as_bytes()).map(drop),
+            Conn::Udp(s) => s.send(msg.as_bytes()).map(drop),
+            Conn::Tcp(s) => s.write_all(format!("{} {}", msg.len(), msg).as_bytes()),
+        }
+    }
+
+    /// Sends one message; a broken connection is reopened and the send
+    /// retried once.
+    fn send(&mut self, msg: &str) -> io::Result<()> {
+        if let Some(Conn::Tcp(s)) = &self.conn {
+            if closed(s) {
+                self.conn = None;
+            }
+        }
+        let mut result = Ok(());
+        for _ in 0..2 {
+            let c = match self.conn.take() {
+                Some(c) => c,
+                None => self.connect()?,
+            };
+            let c = self.conn.insert(c);
+            result = Self::send_on(c, msg);
+            if result.is_ok() {
+                return Ok(());
+            }
+            self.conn = None;
+        }
+        result
+    }
+}
+
+impl Lines for Link {
+    fn write(&mut self, line: &str) -> io::Result<()> {
+        self.send(line)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// True if the daemon has closed its end of `s`. Writing to such a socket
+/// still succeeds locally, and the message is lost with the reset that
+/// follows, so the connection has to be replaced before the write.
+fn closed(s: &TcpStream) -> bool {
+    if s.set_nonblocking(true).is_err() {
+        return true;
+    }
+    let closed = match s.peek(&mut [0; 1]) {
+        Ok(n) => n == 0,
+        Err(e) => e.kind() != io::ErrorKind::WouldBlock,
+    };
+    s.set_nonblocking(false).is_err() || closed
+}
+
+enum Delivery {
+    Inline(Mutex<Link>),
+    /// TCP can stall for `TIMEOUT` per attempt, so it gets a writer thread.
+    Queued(Background),
+}
+
+/// Sends records to a syslog daemon, reconnecting after failures.
+pub struct Syslog {
+    pub protocol: Protocol,
+    pub facility: u8,
+    /// Defaults to the logger name.
+    pub app_name: Option<String>,
+    pub errors: Arc<Errors>,
+    delivery: Delivery,
+}
+
+impl Syslog {
+    pub fn new(address: String, transport: Transport, protocol: Protocol, facility: u8, errors: Errors) -> Self {
+        let errors = Arc::new(errors);
+        let link = Link { address, transport, conn: None, down_until: None };
+        let delivery = match transport {
+            Transport::Tcp => {
+                Delivery::Queued(Background::spawn(link, QUEUE_SIZE, Overflow::DropNewest, errors.clone()))
+            }
+            _ => Delivery::Inline(Mutex::new(link)),
+        };
+        Syslog { protocol, facility, app_name: None, errors, delivery }
+    }
+
+    /// Sends one message, or queues it for TCP. Failures go through the
+    /// `on_error` policy; queued ones surface on a later call.
+    pub fn send(&self, level: LogLevel, name: &str, now: DateTime<Utc>, msg: &str) -> io::Result<()> {
+        let pri = self.facility * 8 + severity(level);
+        let app = self.app_name.as_deref().unwrap_or(name);
+        let msg = message(self.protocol, pri, now, app, msg);
+        match &self.delivery {
+            Delivery::Inline(link) => link.lock().unwrap().send(&msg).or_else(|e| self.errors.handle(e)),
+            Delivery::Queued(b) => {
+                b.send(msg);
+                self.errors.take_pending()
+            }
+        }
+    }
+
+    pub fn flush(&self) -> io::Result<()> {
+        if let Delivery::Queued(b) = &self.delivery {
+            b.flush();
+        }
+        self.errors.take_pending()
+    }
+
+    /// Messages discarded because the TCP queue was full.
+    pub fn dropped(&self) -> u64 {
+        match &self.delivery {
+            Delivery::Inline(_) => 0,
+            Delivery::Queued(b) => b.dropped(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::OnError;
+    use std::io::{BufRead, BufReader};
+    use std::net::TcpListener;
+
+    fn syslog(address: String, transport: Transport, protocol: Protocol) -> Syslog {
+        let errors = Errors::new(&address, OnError::Raise);
+        Syslog::new(address, transport, protocol, facility("local3").unwrap(), errors)
+    }
+
+    /// Reads one octet-counted frame.
+    fn frame(r: &mut impl BufRead) -> String {
+        let mut len = Vec::new();
+        r.read_until(b' ', &mut len).unwrap();
+        let len: usize = std::str::from_utf8(&len).unwrap().trim().parse().unwrap();
+        let mut body = vec![0; len];
+        r.read_exact(&mut body).unwrap();
+        String::from_utf8(body).unwrap()
+    }
+
+    #[test]
+    fn rfc5424_over_unix_datagram() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("log.sock");
+        let listener = UnixDatagram::bind(&path).unwrap();
+        let s = syslog(path.to_string_lossy().into(), Transport::Unix, Protocol::Rfc5424);
+        s.send(LogLevel::WARNING, "chat service", Utc::now(), "slow reply ms=850").unwrap();
+        let mut buf = [0; 1024];
+        let n = listener.recv(&mut buf).unwrap();
+        let got = std::str::from_utf8(&buf[..n]).unwrap();
+        // local3 (19) * 8 + warning (4)
+        assert!(got.starts_with("<156>1 "), "{}", got);
+        let parts: Vec<&str> = got.splitn(8, ' ').collect();
+        assert_eq!(parts[3], "chat_service");
+        assert_eq!(parts[4], std::process::id().to_string());
+        assert_eq!(parts[7], "slow reply ms=850");
+    }
+
+    #[test]
+    fn rfc3164_over_udp() {
+        let listener = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let mut s = syslog(listener.local_addr().unwrap().to_string(), Transport::Udp, Protocol::Rfc3164);
+        s.app_name = Some("treechat".into());
+        s.send(LogLevel::ERROR, "routes", Utc::now(), "boom").unwrap();
+        let mut buf = [0; 1024];
+        let n = listener.recv(&mut buf).unwrap();
+        let got = std::str::from_utf8(&buf[..n]).unwrap();
+        assert!(got.starts_with("<155>"), "{}", got);
+        assert!(got.ends_with(&format!(" treechat[{}]: boom", std::process::id())), "{}", got);
+    }
+
+    #[test]
+    fn tcp_frames_and_reconnects() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let s = syslog(listener.local_addr().unwrap().to_string(), Transport::Tcp, Protocol::Rfc5424);
+        s.send(LogLevel::INFO, "a", Utc::now(), "first\nline").unwrap();
+        let (conn, _) = listener.accept().unwrap();
+        let mut r = BufReader::new(conn);
+        assert!(frame(&mut r).ends_with(" - - first\nline"));
+        // The daemon restarts: the next message has to go out on a new
+        // connection rather than into the dead one.
+        drop(r);
+        s.send(LogLevel::INFO, "a", Utc::now(), "after").unwrap();
+        s.flush().unwrap();
+        listener.set_nonblocking(true).unwrap();
+        let (conn, _) = listener.accept().expect("no reconnect after the listener dropped the connection");
+        conn.set_nonblocking(false).unwrap();
+        assert!(frame(&mut BufReader::new(conn)).ends_with(" - - after"));
+    }
+
+    #[test]
+    fn tcp_sends_do_not_wait_for_the_daemon() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let s = syslog(listener.local_addr().unwrap().to_string(), Transport::Tcp, Protocol::Rfc5424);
+        s.send(LogLevel::INFO, "a", Utc::now(), "hello").unwrap();
+        // Accepted but never read: the socket buffers fill up and the
+        // writer thread blocks, the callers must not.
+        let (conn, _) = listener.accept().unwrap();
+        let big = "x".repeat(64 * 1024);
+        let start = Instant::now();
+        for _ in 0..200 {
+            s.send(LogLevel::INFO, "a", Utc::now(), &big).unwrap();
+        }
+        assert!(start.elapsed() < Duration::from_secs(1), "{:?}", start.elapsed());
+        // Let the writer fail fast so dropping `s` does not wait on it.
+        drop(conn);
+        drop(listener);
+    }
+
+    #[test]
+    fn dropping_is_bounded_when_the_daemon_stops_reading() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let s = syslog(listener.local_addr().unwrap().to_string(), Transport::Tcp, Protocol::Rfc5424);
+        s.send(LogLevel::INFO, "a", Utc::now(), "hello").unwrap();
+        // Kept open but never read, so every write waits out its timeout.
+        let (_conn, _) = listener.accept().unwrap();
+        let big = "x".repeat(64 * 1024);
+        for _ in 0..200 {
+            s.send(LogLevel::INFO, "a", Utc::now(), &big).unwrap();
+        }
+        let start = Instant::now();
+        drop(s);
+        assert!(start.elapsed() < Duration::from_secs(3), "{:?}", start.elapsed());
+    }
+
+    #[test]
+    fn unreachable_daemon_reports_error() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("missing.sock");
+        let s = syslog(path.to_string_lossy().into(), Transport::Unix, Protocol::Rfc5424);
+        assert!(s.send(LogLevel::INFO, "a", Utc::now(), "x").is_err());
+        let errors = Errors::new("missing.sock", OnError::Ignore);
+        let quiet = Syslog::new(path.to_string_lossy().into(), Transport::Unix, Protocol::Rfc5424, 1, errors);
+        quiet.send(LogLevel::INFO, "a", Utc::now(), "x").unwrap();
+        assert_eq!(quiet.errors.suppressed(), 1);
+    }
+}
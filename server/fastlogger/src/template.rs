@@ -0,0 +1,221 @@
+use chrono::format::{Item, StrftimeItems};
+use std::fmt::Write;
+
+use crate::fields;
+use crate::format::{self, Record};
+
+#[derive(Clone, Copy)]
+enum Align {
+    Left,
+    Right,
+    Center,
+}
+
+/// Width and alignment from a `{level:<7}` style spec.
+#[derive(Clone, Copy)]
+struct Pad {
+    align: Align,
+    width: usize,
+}
+
+impl Pad {
+    fn parse(spec: &str) -> Option<Self> {
+        let (align, width) = match spec.chars().next()? {
+            '<' => (Align::Left, &spec[1..]),
+            '>' => (Align::Right, &spec[1..]),
+            '^' => (Align::Center, &spec[1..]),
+            _ => (Align::Left, spec),
+        };
+        Some(Pad { align, width: width.parse().ok()? })
+    }
+
+    fn apply(self, out: &mut String, s: &str) {
+        let fill = self.width.saturating_sub(s.chars().count());
+        let (before, after) = match self.align {
+            Align::Left => (0, fill),
+            Align::Right => (fill, 0),
+            Align::Center => (fill / 2, fill - fill / 2),
+        };
+        out.extend(std::iter::repeat_n(' ', before));
+        out.push_str(s);
+        out.extend(std::iter::repeat_n(' ', after));
+    }
+}
+
+enum Piece {
+    Literal(String),
+    Time(Option<Vec<Item<'static>>>),
+    Level(Option<Pad>),
+    Name(Option<Pad>),
+    Message(Option<Pad>),
+    Fields,
+    Pid(Option<Pad>),
+    Thread(Option<Pad>),
+}
+
+/// A compiled line template such as
+/// `"{time:%H:%M:%S%.3f} {level:<7} [{name}] {message} {fields}"`.
+///
+/// Placeholders are `time` (with an optional strftime spec), `level`, `name`,
+/// `message`, `pid` and `thread`, the OS thread id (with an optional `<N`,
+/// `>N` or `^N` width), and `fields`. `{{` and `}}` produce literal braces.
+pub struct Template {
+    pieces: Vec<Piece>,
+}
+
+fn placeholder(body: &str) -> Result<Piece, String> {
+    let (key, spec) = match body.split_once(':') {
+        Some((k, s)) => (k.trim(), Some(s)),
+        None => (body.trim(), None),
+    };
+    if key == "time" {
+        return match spec {
+            None => Ok(Piece::Time(None)),
+            Some(s) => StrftimeItems::new(s)
+                .parse_to_owned()
+                .map(|items| Piece::Time(Some(items)))
+                .map_err(|_| format!("invalid time format {:?}", s)),
+        };
+    }
+    let pad = match spec {
+        None => None,
+        Some(s) => Some(Pad::parse(s).ok_or_else(|| format!("invalid width {:?} for {{{}}}", s, key))?),
+    };
+    match key {
+        "level" => Ok(Piece::Level(pad)),
+        "name" => Ok(Piece::Name(pad)),
+        "message" => Ok(Piece::Message(pad)),
+        "pid" => Ok(Piece::Pid(pad)),
+        "thread" => Ok(Piece::Thread(pad)),
+        "fields" if pad.is_none() => Ok(Piece::Fields),
+        "fields" => Err("{fields} takes no width".into()),
+        _ => Err(format!("unknown placeholder {{{}}}", key)),
+    }
+}
+
+impl Template {
+    pub fn compile(src: &str) -> Result<Self, String> {
+        let mut pieces = Vec::new();
+        let mut lit = String::new();
+        let mut chars = src.chars().peekable();
+        while let Some(c) = chars.next() {
+            match c {
+                '{' if chars.peek() == Some(&'{') => {
+                    chars.next();
+                    lit.push('{');
+                }
+                '}' if chars.peek() == Some(&'}') => {
+                    chars.next();
+                    lit.push('}');
+                }
+                '{' => {
+                    let mut body = String::new();
+                    loop {
+                        match chars.next() {
+                            Some('}') => break,
+                            Some(c) => body.push(c),
+                            None => return Err(format!("unclosed placeholder {{{}", body)),
+                        }
+                    }
+                    if !lit.is_empty() {
+                        pieces.push(Piece::Literal(std::mem::take(&mut lit)));
+                    }
+                    pieces.push(placeholder(&body)?);
+                }
+                '}' => return Err("unmatched '}' (use '}}' for a literal brace)".into()),
+                c => lit.push(c),
+            }
+        }
+        if !lit.is_empty() {
+            pieces.push(Piece::Literal(lit));
+        }
+        Ok(Template { pieces })
+    }
+
+    pub fn render(&self, r: &Record) -> String {
+        let mut out = String::with_capacity(128);
+        let text = |out: &mut String, pad: Option<Pad>, s: &str| match pad {
+            Some(p) => p.apply(out, s),
+            None => out.push_str(s),
+        };
+        // Where the last literal started, so an empty `{fields}` can take
+        // the separator before it along without touching the message.
+        let mut literal_at = 0;
+        for piece in &self.pieces {
+            match piece {
+                Piece::Literal(s) => out.push_str(s),
+                Piece::Time(None) => out.push_str(r.ts),
+                Piece::Time(Some(items)) => {
+                    write!(out, "{}", r.time.format_with_items(items.iter())).ok();
+                }
+                Piece::Level(pad) => text(&mut out, *pad, r.level),
+                Piece::Name(pad) => text(&mut out, *pad, r.name),
+                Piece::Message(pad) => text(&mut out, *pad, r.msg),
+                Piece::Fields if r.fields.is_empty() => {
+                    let kept = out[literal_at..].trim_end_matches(' ').len();
+                    out.truncate(literal_at + kept);
+                }
+                Piece::Fields => out.push_str(&fields::render_text(r.fields)),
+                Piece::Pid(pad) => text(&mut out, *pad, &std::process::id().to_string()),
+                Piece::Thread(pad) => text(&mut out, *pad, &format::thread_id().to_string()),
+            }
+            if !matches!(piece, Piece::Literal(_)) {
+                literal_at = out.len();
+            }
+        }
+        out.push('\n');
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{DateTime, FixedOffset};
+    use serde_json::json;
+
+    fn render(src: &str, msg: &str, fields: &fields::Fields) -> String {
+        let time = DateTime::<FixedOffset>::parse_from_rfc3339("2026-10-16T09:05:03.250+02:00").unwrap();
+        let r = Record { time: &time, ts: "TS", level: "INFO", name: "chat", msg, fields, exc: None };
+        Template::compile(src).unwrap().render(&r)
+    }
+
+    fn error(src: &str) -> String {
+        Template::compile(src).err().expect("should not compile")
+    }
+
+    #[test]
+    fn placeholders_and_escapes() {
+        let out = render("{{{time}}} {time:%H:%M:%S%.3f} [{name}] {message}", "hi", &Vec::new());
+        assert_eq!(out, "{TS} 09:05:03.250 [chat] hi\n");
+        assert_eq!(render("{{level}} }}", "", &Vec::new()), "{level} }\n");
+    }
+
+    #[test]
+    fn widths_and_alignment() {
+        let aligned = render("[{level:<6}][{level:>6}][{level:^7}][{level:6}]", "", &Vec::new());
+        assert_eq!(aligned, "[INFO  ][  INFO][ INFO  ][INFO  ]\n");
+        // Values longer than the width are not cut.
+        assert_eq!(render("[{name:2}]", "", &Vec::new()), "[chat]\n");
+    }
+
+    #[test]
+    fn empty_fields_drop_their_separator_only() {
+        assert_eq!(render("{message} {fields}", "done", &Vec::new()), "done\n");
+        assert_eq!(render("{message}{fields}", "padded  ", &Vec::new()), "padded  \n");
+        assert_eq!(render("{message} {fields}", "padded  ", &Vec::new()), "padded  \n");
+        let fields = vec![("user".to_string(), json!("ana"))];
+        assert_eq!(render("{message} {fields}", "done", &fields), "done user=ana\n");
+        assert_eq!(render("{message} {fields} |", "done", &Vec::new()), "done |\n");
+    }
+
+    #[test]
+    fn rejects_bad_templates() {
+        assert_eq!(error("{message"), "unclosed placeholder {message");
+        assert_eq!(error("oops }"), "unmatched '}' (use '}}' for a literal brace)");
+        assert_eq!(error("{nope}"), "unknown placeholder {nope}");
+        assert_eq!(error("{level:<x}"), "invalid width \"<x\" for {level}");
+        assert_eq!(error("{fields:10}"), "{fields} takes no width");
+        assert!(error("{time:%Q}").starts_with("invalid time format"));
+    }
+}
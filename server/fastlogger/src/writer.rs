@@ -0,0 +1,338 @@
+use crossbeam_channel::{bounded, select, unbounded, Receiver, RecvTimeoutError, Sender, TrySendError};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::io;
+use std::sync::{Arc, Mutex, RwLock};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+use crate::error::{Errors, OnError};
+use crate::file::FileTarget;
+
+/// What a background writer does when its queue is full.
+#[derive(Clone, Copy, PartialEq)]
+pub enum Overflow {
+    Block,
+    DropNewest,
+    DropOldest,
+}
+
+impl Overflow {
+    pub fn parse(s: &str) -> Option<Self> {
+        match s.to_lowercase().replace('-', "_").as_str() {
+            "block" => Some(Overflow::Block),
+            "drop_newest" => Some(Overflow::DropNewest),
+            "drop_oldest" => Some(Overflow::DropOldest),
+            _ => None,
+        }
+    }
+}
+
+/// Where a background writer delivers its lines.
+pub trait Lines: Send + 'static {
+    fn write(&mut self, line: &str) -> io::Result<()>;
+    fn flush(&mut self) -> io::Result<()>;
+}
+
+impl Lines for FileTarget {
+    fn write(&mut self, line: &str) -> io::Result<()> {
+        FileTarget::write(self, line)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        FileTarget::flush(self)
+    }
+}
+
+/// How long dropping a background writer waits for its queue to drain;
+/// lines still queued after that are dropped and counted.
+const DRAIN: Duration = Duration::from_secs(2);
+
+/// A bounded queue and the thread that drains it into a `Lines`.
+pub struct Background {
+    tx: Option<Sender<String>>,
+    rx: Receiver<String>,
+    /// Flush requests, kept apart from lines so overflow never evicts one.
+    flush_tx: Option<Sender<Sender<()>>>,
+    overflow: Overflow,
+    dropped: Arc<AtomicU64>,
+    /// Disconnects when the thread is done.
+    done: Receiver<()>,
+    handle: Option<JoinHandle<()>>,
+}
+
+impl Background {
+    pub fn spawn(mut target: impl Lines, capacity: usize, overflow: Overflow, errors: Arc<Errors>) -> Self {
+        let (tx, rx) = bounded::<String>(capacity.max(1));
+        let (flush_tx, flush_rx) = unbounded::<Sender<()>>();
+        let (done_tx, done) = bounded::<()>(0);
+        let dropped = Arc::new(AtomicU64::new(0));
+        let lines = rx.clone();
+        let lost = dropped.clone();
+        let handle = std::thread::Builder::new()
+            .name("fastlogger-writer".into())
+            .spawn(move || {
+                let _done = done_tx;
+                let report = |res: io::Result<()>| {
+                    if let Err(e) = res {
+                        errors.defer(e);
+                    }
+                };
+                loop {
+                    select! {
+                        recv(lines) -> line => match line {
+                            Ok(line) => {
+                                report(target.write(&line));
+                                if lines.is_empty() {
+                                    report(target.flush());
+                                }
+                            }
+                            Err(_) => break,
+                        },
+                        // Lines queued before the request are written first.
+                        recv(flush_rx) -> ack => {
+                            for line in lines.try_iter() {
+                                report(target.write(&line));
+                            }
+                            report(target.flush());
+                            match ack {
+                                Ok(ack) => ack.send(()).ok(),
+                                Err(_) => break,
+                            };
+                        }
+                    }
+                }
+                let deadline = Instant::now() + DRAIN;
+                for line in lines.try_iter() {
+                    if Instant::now() >= deadline {
+                        lost.fetch_add(1 + lines.len() as u64, Ordering::Relaxed);
+                        break;
+                    }
+                    target.write(&line).ok();
+                }
+                target.flush().ok();
+            })
+            .expect("failed to spawn fastlogger writer thread");
+        Background {
+            tx: Some(tx),
+            rx,
+            flush_tx: Some(flush_tx),
+            overflow,
+            dropped,
+            done,
+            handle: Some(handle),
+        }
+    }
+
+    pub fn send(&self, mut line: String) {
+        let tx = match &self.tx {
+            Some(tx) => tx,
+            None => return,
+        };
+        loop {
+            match self.overflow {
+                Overflow::Block => {
+                    tx.send(line).ok();
+                    return;
+                }
+                Overflow::DropNewest => {
+                    if let Err(TrySendError::Full(_)) = tx.try_send(line) {
+                        self.dropped.fetch_add(1, Ordering::Relaxed);
+                    }
+                    return;
+                }
+                Overflow::DropOldest => match tx.try_send(line) {
+                    Err(TrySendError::Full(l)) => {
+                        if self.rx.try_recv().is_ok() {
+                            self.dropped.fetch_add(1, Ordering::Relaxed);
+                        }
+                        line = l;
+                    }
+                    _ => return,
+                },
+            }
+        }
+    }
+
+    /// Waits until every line queued so far has been written.
+    pub fn flush(&self) {
+        if let Some(tx) = &self.flush_tx {
+            let (ack_tx, ack_rx) = bounded(1);
+            if tx.send(ack_tx).is_ok() {
+                ack_rx.recv().ok();
+            }
+        }
+    }
+
+    pub fn dropped(&self) -> u64 {
+        self.dropped.load(Ordering::Relaxed)
+    }
+}
+
+impl Drop for Background {
+    /// Waits at most `DRAIN` for the queue to be written: this often runs
+    /// with the GIL held, and a peer that stopped reading could otherwise
+    /// hold it for as long as the queue takes to time out line by line.
+    fn drop(&mut self) {
+        self.tx.take();
+        self.flush_tx.take();
+        if let Err(RecvTimeoutError::Disconnected) = self.done.recv_timeout(DRAIN) {
+            if let Some(h) = self.handle.take() {
+                h.join().ok();
+            }
+        }
+    }
+}
+
+enum Kind {
+    Direct(Mutex<FileTarget>),
+    Background(Background),
+}
+
+/// Delivers formatted lines to a file, either inline or via a writer thread.
+pub struct Writer {
+    /// Swapped by `reconfigure`; everyone holding the writer follows.
+    kind: RwLock<Kind>,
+    errors: Arc<Errors>,
+}
+
+impl Writer {
+    pub fn direct(target: FileTarget, errors: Errors) -> Self {
+        Writer { kind: RwLock::new(Kind::Direct(Mutex::new(target))), errors: Arc::new(errors) }
+    }
+
+    pub fn background(target: FileTarget, capacity: usize, overflow: Overflow, errors: Errors) -> Self {
+        let errors = Arc::new(errors);
+        let b = Background::spawn(target, capacity, overflow, errors.clone());
+        Writer { kind: RwLock::new(Kind::Background(b)), errors }
+    }
+
+    /// Switches to new settings in place. The old handle is flushed, `open`
+    /// opens the path again, and only then is the old handle closed, so a
+    /// failed open leaves the writer as it was. The write lock keeps every
+    /// line out of both handles meanwhile, and neither holds the sidecar
+    /// lock, so there is still one rotation state for the path. `queue` is
+    /// the capacity and overflow policy of a background writer, `None` for
+    /// a direct one.
+    pub fn reconfigure<E>(
+        &self,
+        policy: OnError,
+        queue: Option<(usize, Overflow)>,
+        open: impl FnOnce() -> Result<FileTarget, E>,
+    ) -> Result<(), E> {
+        let mut kind = self.kind.write().unwrap();
+        let flushed = match &*kind {
+            Kind::Direct(t) => t.lock().unwrap().flush(),
+            Kind::Background(b) => {
+                b.flush();
+                Ok(())
+            }
+        };
+        if let Err(e) = flushed {
+            self.errors.defer(e);
+        }
+        let target = open()?;
+        self.errors.set_policy(policy);
+        *kind = match queue {
+            Some((capacity, overflow)) => {
+                Kind::Background(Background::spawn(target, capacity, overflow, self.errors.clone()))
+            }
+            None => Kind::Direct(Mutex::new(target)),
+        };
+        Ok(())
+    }
+
+    /// Writes `line`; errors not swallowed by the `on_error` policy are
+    /// returned, including ones the background thread hit since the last call.
+    pub fn write(&self, line: String) -> io::Result<()> {
+        match &*self.kind.read().unwrap() {
+            Kind::Direct(t) => {
+                let mut t = t.lock().unwrap();
+                t.write(&line).and_then(|_| t.flush()).or_else(|e| self.errors.handle(e))
+            }
+            Kind::Background(b) => {
+                b.send(line);
+                self.errors.take_pending()
+            }
+        }
+    }
+
+    pub fn flush(&self) -> io::Result<()> {
+        match &*self.kind.read().unwrap() {
+            Kind::Direct(t) => t.lock().unwrap().flush().or_else(|e| self.errors.handle(e)),
+            Kind::Background(b) => {
+                b.flush();
+                self.errors.take_pending()
+            }
+        }
+    }
+
+    pub fn dropped(&self) -> u64 {
+        match &*self.kind.read().unwrap() {
+            Kind::Direct(_) => 0,
+            Kind::Background(b) => b.dropped(),
+        }
+    }
+
+    pub fn errors(&self) -> &Errors {
+        &self.errors
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::FileSpec;
+    use std::ffi::CString;
+    use std::io::Read;
+    use std::time::Duration;
+
+    #[test]
+    fn drop_oldest_keeps_pending_flush() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("pipe.log").to_string_lossy().to_string();
+        let c = CString::new(path.clone()).unwrap();
+        assert_eq!(unsafe { libc::mkfifo(c.as_ptr(), 0o600) }, 0);
+        let (opened_tx, opened) = crossbeam_channel::bounded(1);
+        let (go_tx, go) = crossbeam_channel::bounded::<()>(1);
+        let reader = {
+            let path = path.clone();
+            std::thread::spawn(move || {
+                let mut f = std::fs::File::open(&path).unwrap();
+                opened_tx.send(()).unwrap();
+                go.recv().unwrap();
+                let mut out = String::new();
+                f.read_to_string(&mut out).unwrap();
+                out
+            })
+        };
+        let target = FileTarget::new(path.clone(), FileSpec::new(path.clone()).rotation).unwrap();
+        opened.recv().unwrap();
+        let w = Arc::new(Writer::background(target, 2, Overflow::DropOldest, Errors::new(&path, OnError::Raise)));
+        // Larger than the pipe buffer: the writer thread blocks until the
+        // reader starts.
+        w.write(format!("{}\n", "x".repeat(256 * 1024))).unwrap();
+        std::thread::sleep(Duration::from_millis(100));
+        let (done_tx, done) = crossbeam_channel::bounded(1);
+        let flusher = {
+            let w = w.clone();
+            std::thread::spawn(move || {
+                w.flush().unwrap();
+                done_tx.send(()).unwrap();
+            })
+        };
+        std::thread::sleep(Duration::from_millis(50));
+        for i in 0..5 {
+            w.write(format!("line {}\n", i)).unwrap();
+        }
+        let early = done.recv_timeout(Duration::from_millis(200)).is_ok();
+        let dropped = w.dropped();
+        go_tx.send(()).unwrap();
+        assert!(!early, "flush returned before the queued lines were written");
+        assert_eq!(dropped, 3);
+        done.recv_timeout(Duration::from_secs(5)).unwrap();
+        flusher.join().unwrap();
+        drop(w);
+        let out = reader.join().unwrap();
+        assert!(out.ends_with("line 3\nline 4\n"), "{:?}", &out[out.len() - 40..]);
+    }
+}
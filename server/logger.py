@@ -1,3 +1,4 @@
+from fastlogger import ConsoleSink, FileSink, OtlpSink, RingBufferSink, get_logger
 from fastlogger import Logger as FastLogger
 from pathlib import Path
 from server.config import settings
@@ -7,25 +8,59 @@ class LogConfig:
     LOG_DIR = Path("logs")
     MAX_BYTES = 10 * 1024 * 1024
     BACKUP_COUNT = 5
+    MAX_MESSAGE_BYTES = 64 * 1024
     LOG_LEVEL = "INFO"
+    RECENT_CAPACITY = 2000
+    # A failed write (disk full, say) is reported on stderr rather than
+    # raised into the request that logged it.
+    ON_ERROR = "stderr"
 
 
 LogConfig.LOG_DIR.mkdir(exist_ok=True)
 
+# Shared by every logger: WARNING and up also land in errors.log.
+_errors = FileSink(
+    str(LogConfig.LOG_DIR / "errors.log"),
+    level="WARNING",
+    max_bytes=LogConfig.MAX_BYTES,
+    backup_count=LogConfig.BACKUP_COUNT,
+    max_message_bytes=LogConfig.MAX_MESSAGE_BYTES,
+    on_error=LogConfig.ON_ERROR,
+)
+_console = ConsoleSink(level="INFO", max_message_bytes=LogConfig.MAX_MESSAGE_BYTES)
+# The last records from every logger, for the debug log route.
+recent_logs = RingBufferSink(LogConfig.RECENT_CAPACITY, max_message_bytes=LogConfig.MAX_MESSAGE_BYTES)
+_sinks = [_errors, _console, recent_logs]
+
+if settings.OTLP_ENDPOINT:
+    # Records the collector cannot take are kept in otlp_fallback.log.
+    _sinks.append(
+        OtlpSink(
+            settings.OTLP_ENDPOINT,
+            service_name="treechat",
+            fallback=FileSink(
+                str(LogConfig.LOG_DIR / "otlp_fallback.log"),
+                max_bytes=LogConfig.MAX_BYTES,
+                backup_count=LogConfig.BACKUP_COUNT,
+                on_error=LogConfig.ON_ERROR,
+            ),
+            max_message_bytes=LogConfig.MAX_MESSAGE_BYTES,
+            on_error=LogConfig.ON_ERROR,
+        )
+    )
 
-class Logger:
-    _instances = {}
 
+class Logger:
     @classmethod
     def get(cls, name: str) -> FastLogger:
-        if name not in cls._instances:
-            log_file = LogConfig.LOG_DIR / f"{name}.log"
-            cls._instances[name] = FastLogger(
-                name=name,
-                path=str(log_file),
-                max_bytes=LogConfig.MAX_BYTES,
-                backup_count=LogConfig.BACKUP_COUNT,
-                show_output=True,
-                level=LogConfig.LOG_LEVEL,
-            )
-        return cls._instances[name]
+        # fastlogger caches loggers by name and shares one writer per file.
+        return get_logger(
+            name,
+            path=str(LogConfig.LOG_DIR / f"{name}.log"),
+            max_bytes=LogConfig.MAX_BYTES,
+            backup_count=LogConfig.BACKUP_COUNT,
+            max_message_bytes=LogConfig.MAX_MESSAGE_BYTES,
+            level=LogConfig.LOG_LEVEL,
+            on_error=LogConfig.ON_ERROR,
+            sinks=_sinks,
+        )
@@ -1,3 +1,6 @@
+import uuid
+
+import fastlogger
 from fastapi import FastAPI, Request
 from fastapi.middleware.cors import CORSMiddleware
 from motor.motor_asyncio import AsyncIOMotorClient
@@ -6,7 +9,7 @@ from contextlib import asynccontextmanager
 
 from server.config import settings
 from server.models import Task, Message, Context, Project, Folder
-from server.routes import tasks, messages, contexts, chat, folders, search, summary
+from server.routes import tasks, messages, contexts, chat, folders, search, summary, debug
 from server.logger import Logger
 from server.utils.route_logger import route_logger
 
@@ -58,11 +61,14 @@ app.add_middleware(
 
 @app.middleware("http")
 async def log_requests(request: Request, call_next):
-    logger.info(f"{request.method} {request.url.path}")
-    response = await call_next(request)
-    logger.debug(
-        f"{request.method} {request.url.path} - Status: {response.status_code}"
-    )
+    request_id = request.headers.get("x-request-id") or uuid.uuid4().hex[:12]
+    with fastlogger.context(request_id=request_id):
+        logger.info(f"{request.method} {request.url.path}")
+        response = await call_next(request)
+        logger.debug(
+            f"{request.method} {request.url.path} - Status: {response.status_code}"
+        )
+    response.headers["x-request-id"] = request_id
     return response
 
 
@@ -74,6 +80,8 @@ app.include_router(chat.router, prefix="/api/chat", tags=["chat"])
 app.include_router(folders.router, prefix="/api/folders", tags=["folders"])
 app.include_router(search.router, prefix="/api/search", tags=["search"])
 app.include_router(summary.router, prefix="/api/summary", tags=["summary"])
+if settings.DEBUG_LOG_ROUTE:
+    app.include_router(debug.router, prefix="/api/debug", tags=["debug"])
 
 
 @app.get("/")
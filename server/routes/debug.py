@@ -0,0 +1,35 @@
+"""
+Debug Routes - recent log records, no shell access needed
+
+- GET /api/debug/logs - the newest records kept in memory, filtered by
+  level, logger name prefix, time and request id
+
+Only mounted when settings.DEBUG_LOG_ROUTE is set.
+"""
+
+from datetime import datetime
+from typing import List, Optional
+
+from fastapi import APIRouter, HTTPException, Query
+
+from server.logger import recent_logs
+from server.utils.route_logger import route_logger
+
+router = APIRouter()
+
+
+@router.get("/logs", response_model=List[dict])
+@route_logger
+async def get_recent_logs(
+    level: Optional[str] = Query(None, description="Minimum level, e.g. WARNING"),
+    logger: Optional[str] = Query(None, description="Logger name prefix, e.g. chat_service"),
+    since: Optional[datetime] = Query(None, description="ISO time; read as UTC without an offset"),
+    request_id: Optional[str] = None,
+    limit: int = Query(200, ge=1, le=2000),
+):
+    """What the services logged most recently, oldest first"""
+    fields = {"request_id": request_id} if request_id else {}
+    try:
+        return recent_logs.recent(level=level, name_prefix=logger, since=since, limit=limit, **fields)
+    except ValueError as e:
+        raise HTTPException(status_code=400, detail=str(e))
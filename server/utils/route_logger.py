@@ -10,7 +10,6 @@ Provides a decorator to wrap route handlers with:
 
 import functools
 import time
-import traceback
 from typing import Callable, Any
 from fastapi import Request, HTTPException
 from server.logger import Logger
@@ -63,7 +62,11 @@ def route_logger(func: Callable) -> Callable:
             execution_time = (time.time() - start_time) * 1000  # ms
             
             # Log success
-            logger.info(f"[SUCCESS] {route_name} - {execution_time:.2f}ms")
+            logger.info(
+                f"[SUCCESS] {route_name}",
+                route=route_name,
+                elapsed_ms=round(execution_time, 2),
+            )
             
             return result
             
@@ -71,8 +74,11 @@ def route_logger(func: Callable) -> Callable:
             # Log HTTP exceptions (expected errors like 404, 400, etc.)
             execution_time = (time.time() - start_time) * 1000
             logger.warning(
-                f"[HTTP_ERROR] {route_name} - {execution_time:.2f}ms - "
-                f"Status: {e.status_code} - Detail: {e.detail}"
+                f"[HTTP_ERROR] {route_name}",
+                route=route_name,
+                elapsed_ms=round(execution_time, 2),
+                status_code=e.status_code,
+                detail=e.detail,
             )
             raise  # Re-raise to let FastAPI handle it
             
@@ -80,12 +86,13 @@ def route_logger(func: Callable) -> Callable:
             # Log unexpected exceptions with full traceback
             execution_time = (time.time() - start_time) * 1000
             error_msg = str(e)
-            tb = traceback.format_exc()
             
-            logger.error(
-                f"[EXCEPTION] {route_name} - {execution_time:.2f}ms\n"
-                f"Error: {error_msg}\n"
-                f"Traceback:\n{tb}"
+            logger.exception(
+                f"[EXCEPTION] {route_name}",
+                route=route_name,
+                elapsed_ms=round(execution_time, 2),
+                status_code=500,
+                error=error_msg,
             )
             
             # Re-raise as HTTP 500
@@ -120,7 +127,11 @@ def route_logger(func: Callable) -> Callable:
             execution_time = (time.time() - start_time) * 1000  # ms
             
             # Log success
-            logger.info(f"[SUCCESS] {route_name} - {execution_time:.2f}ms")
+            logger.info(
+                f"[SUCCESS] {route_name}",
+                route=route_name,
+                elapsed_ms=round(execution_time, 2),
+            )
             
             return result
             
@@ -128,8 +139,11 @@ def route_logger(func: Callable) -> Callable:
             # Log HTTP exceptions
             execution_time = (time.time() - start_time) * 1000
             logger.warning(
-                f"[HTTP_ERROR] {route_name} - {execution_time:.2f}ms - "
-                f"Status: {e.status_code} - Detail: {e.detail}"
+                f"[HTTP_ERROR] {route_name}",
+                route=route_name,
+                elapsed_ms=round(execution_time, 2),
+                status_code=e.status_code,
+                detail=e.detail,
             )
             raise
             
@@ -137,12 +151,13 @@ def route_logger(func: Callable) -> Callable:
             # Log unexpected exceptions
             execution_time = (time.time() - start_time) * 1000
             error_msg = str(e)
-            tb = traceback.format_exc()
             
-            logger.error(
-                f"[EXCEPTION] {route_name} - {execution_time:.2f}ms\n"
-                f"Error: {error_msg}\n"
-                f"Traceback:\n{tb}"
+            logger.exception(
+                f"[EXCEPTION] {route_name}",
+                route=route_name,
+                elapsed_ms=round(execution_time, 2),
+                status_code=500,
+                error=error_msg,
             )
             
             raise HTTPException(